@@ -0,0 +1,130 @@
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime, TimeDelta, Timelike};
+use crate::value::*;
+
+/**************************************************************************************************/
+/*                                            LITERALS                                            */
+/**************************************************************************************************/
+
+/// Tells whether the text of a `Token::Number` is a date or a time rather than a number.
+pub fn is_date_time(text: &str) -> bool {
+    text.contains(':') || (text.len() >= 10 && text.as_bytes()[4] == b'-' && text.as_bytes()[7] == b'-')
+}
+
+/// Reads the text of a date and time `Token::Number`: `2026-10-17`, `18:30`, `9:15:30.5` or
+/// `2026-10-17T18:30`. Times are local and without time zone.
+pub fn parse_date_time(text: &str) -> Option<Value> {
+    match text.split_once('T') {
+        Some((date, time)) => Some(Value::DateTime(NaiveDateTime::new(parse_date(date)?, parse_time(time)?))),
+        None if text.contains(':') => parse_time(text).map(Value::Time),
+        None => parse_date(text).map(Value::Date),
+    }
+}
+
+fn parse_date(text: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(text, "%Y-%m-%d").ok()
+}
+
+fn parse_time(text: &str) -> Option<NaiveTime> {
+    match text.matches(':').count() {
+        1 => NaiveTime::parse_from_str(text, "%H:%M").ok(),
+        _ => NaiveTime::parse_from_str(text, "%H:%M:%S%.f").ok(),
+    }
+}
+
+/// Gives the date of a date, or of a date and time.
+pub fn get_date(value: &Value) -> Option<NaiveDate> {
+    match value {
+        Value::Date(date) => Some(*date),
+        Value::DateTime(date_time) => Some(date_time.date()),
+        _ => None,
+    }
+}
+
+/// Gives the time of day of a time, or of a date and time.
+pub fn get_time(value: &Value) -> Option<NaiveTime> {
+    match value {
+        Value::Time(time) => Some(*time),
+        Value::DateTime(date_time) => Some(date_time.time()),
+        _ => None,
+    }
+}
+
+/// Reads a date as its midnight, so that it can be compared with or subtracted from a date and time.
+pub fn get_date_time(value: &Value) -> Option<NaiveDateTime> {
+    match value {
+        Value::Date(date) => Some(date.and_time(NaiveTime::MIN)),
+        Value::DateTime(date_time) => Some(*date_time),
+        _ => None,
+    }
+}
+
+/// Writes a time of day, omitting zero seconds as in `18:30`.
+pub fn format_time(time: &NaiveTime) -> String {
+    match (time.second(), time.nanosecond()) {
+        (0, 0) => time.format("%H:%M").to_string(),
+        _ => time.format("%H:%M:%S%.f").to_string(),
+    }
+}
+
+/**************************************************************************************************/
+/*                                           DURATIONS                                            */
+/**************************************************************************************************/
+
+/// Gives the duration of a number of seconds, to the nearest nanosecond.
+pub fn from_seconds(seconds: f64) -> Option<TimeDelta> {
+    let whole = seconds.trunc();
+    if !seconds.is_finite() || whole.abs() >= (i64::MAX / 1000) as f64 {
+        return None;
+    }
+    let nanoseconds = ((seconds - whole) * 1e9).round() as i64;
+    TimeDelta::try_seconds(whole as i64)?.checked_add(&TimeDelta::nanoseconds(nanoseconds))
+}
+
+pub fn get_seconds(duration: &TimeDelta) -> f64 {
+    duration.num_seconds() as f64 + duration.subsec_nanos() as f64 / 1e9
+}
+
+/// Writes a duration in days, hours, minutes and seconds, as `1d 2h 30min` or `-9h 15min`.
+pub fn format_duration(duration: &TimeDelta) -> String {
+    let sign = if *duration < TimeDelta::zero() { "-" } else { "" };
+    let duration = duration.abs();
+    let seconds = duration.num_seconds();
+    let parts = [(seconds / 86_400, "d"), (seconds / 3_600 % 24, "h"), (seconds / 60 % 60, "min")];
+    let mut words: Vec<String> = parts.iter()
+        .filter(|(count, _)| *count != 0)
+        .map(|(count, unit)| format!("{}{}", count, unit))
+        .collect();
+    let rest = (seconds % 60) as f64 + duration.subsec_nanos() as f64 / 1e9;
+    if rest != 0.0 || words.is_empty() {
+        words.push(format!("{}s", Value::Number(rest)));
+    }
+    format!("{}{}", sign, words.join(" "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_dates_and_times() {
+        assert!(is_date_time("2026-10-17") && is_date_time("18:30") && !is_date_time("2026"));
+        assert!(matches!(parse_date_time("2026-10-17"), Some(Value::Date(date)) if date.to_string() == "2026-10-17"));
+        assert!(matches!(parse_date_time("9:15:30.5"), Some(Value::Time(time)) if format_time(&time) == "09:15:30.500"));
+        assert!(matches!(parse_date_time("2026-10-17T18:30"), Some(Value::DateTime(_))));
+        assert!(parse_date_time("2026-02-30").is_none());
+        assert!(parse_date_time("24:00").is_none());
+        let date = parse_date_time("2026-10-17").unwrap();
+        assert_eq!(get_date_time(&date).map(|date_time| date_time.to_string()), Some(String::from("2026-10-17 00:00:00")));
+        assert!(get_time(&date).is_none());
+    }
+
+    #[test]
+    fn writes_durations_in_days_hours_and_minutes() {
+        assert_eq!(format_duration(&from_seconds(33_300.0).unwrap()), "9h 15min");
+        assert_eq!(format_duration(&from_seconds(-33_300.0).unwrap()), "-9h 15min");
+        assert_eq!(format_duration(&from_seconds(95_430.5).unwrap()), "1d 2h 30min 30.5s");
+        assert_eq!(format_duration(&TimeDelta::zero()), "0s");
+        assert_eq!(get_seconds(&from_seconds(1.25).unwrap()), 1.25);
+        assert!(from_seconds(f64::INFINITY).is_none());
+    }
+}
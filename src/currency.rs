@@ -0,0 +1,254 @@
+use crate::value::*;
+use num_bigint::BigInt;
+use num_rational::BigRational;
+use num_traits::{Signed, Zero};
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+
+/**************************************************************************************************/
+/*                                            CURRENCY                                            */
+/**************************************************************************************************/
+
+#[derive(Debug, PartialEq)]
+pub struct Currency {
+    /// The ISO 4217 code, as `EUR`.
+    pub code: &'static str,
+    pub symbol: Option<&'static str>,
+    /// Number of digits of the minor unit, `2` for cents and `0` for the yen.
+    pub minor: u32,
+}
+
+const fn currency(code: &'static str, symbol: Option<&'static str>, minor: u32) -> Currency {
+    Currency { code, symbol, minor }
+}
+
+static CURRENCIES: [Currency; 40] = [
+    currency("USD", Some("$"), 2),
+    currency("EUR", Some("€"), 2),
+    currency("GBP", Some("£"), 2),
+    currency("JPY", Some("¥"), 0),
+    currency("CHF", None, 2),
+    currency("CAD", None, 2),
+    currency("AUD", None, 2),
+    currency("NZD", None, 2),
+    currency("CNY", None, 2),
+    currency("HKD", None, 2),
+    currency("SGD", None, 2),
+    currency("SEK", None, 2),
+    currency("NOK", None, 2),
+    currency("DKK", None, 2),
+    currency("ISK", None, 0),
+    currency("PLN", None, 2),
+    currency("CZK", None, 2),
+    currency("HUF", None, 2),
+    currency("RON", None, 2),
+    currency("BGN", None, 2),
+    currency("TRY", Some("₺"), 2),
+    currency("RUB", Some("₽"), 2),
+    currency("UAH", None, 2),
+    currency("INR", Some("₹"), 2),
+    currency("KRW", Some("₩"), 0),
+    currency("IDR", None, 2),
+    currency("THB", Some("฿"), 2),
+    currency("PHP", Some("₱"), 2),
+    currency("VND", Some("₫"), 0),
+    currency("MYR", None, 2),
+    currency("ILS", Some("₪"), 2),
+    currency("AED", None, 2),
+    currency("SAR", None, 2),
+    currency("BHD", None, 3),
+    currency("KWD", None, 3),
+    currency("OMR", None, 3),
+    currency("MXN", None, 2),
+    currency("BRL", None, 2),
+    currency("CLP", None, 0),
+    currency("ZAR", None, 2),
+];
+
+/// Finds a currency by code or by symbol.
+pub fn get_currency(name: &str) -> Option<&'static Currency> {
+    CURRENCIES.iter().find(|currency| currency.code == name || currency.symbol == Some(name))
+}
+
+/**************************************************************************************************/
+/*                                             MONEY                                              */
+/**************************************************************************************************/
+
+/// An exact amount, rounded to the minor unit of its currency.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Money {
+    pub amount: BigRational,
+    pub currency: &'static Currency,
+    /// When the exchange rate giving this amount was published, if a conversion was needed.
+    pub timestamp: Option<String>,
+}
+
+impl Money {
+    /// Writes the amount with all the digits of the minor unit, as `12.50`.
+    pub fn get_amount(&self) -> String {
+        let scale = num_traits::pow(BigInt::from(10), self.currency.minor as usize);
+        let units = (&self.amount * &scale).round().to_integer();
+        let digits = format!("{:0>width$}", units.abs(), width = self.currency.minor as usize + 1);
+        let (whole, fraction) = digits.split_at(digits.len() - self.currency.minor as usize);
+        let sign = if units.is_negative() { "-" } else { "" };
+        match fraction.is_empty() {
+            true => format!("{}{}", sign, whole),
+            false => format!("{}{}.{}", sign, whole, fraction),
+        }
+    }
+}
+
+impl Display for Money {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.get_amount(), self.currency.code)
+    }
+}
+
+/**************************************************************************************************/
+/*                                             RATES                                              */
+/**************************************************************************************************/
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rate {
+    /// Units of the currency buying one unit of the base currency of the table.
+    pub value: BigRational,
+    pub timestamp: Option<String>,
+}
+
+/// Exchange rates against a common base currency, supplied by the user rather than fetched.
+#[derive(Debug, Clone, Default)]
+pub struct Rates {
+    rates: HashMap<String, Rate>,
+}
+
+impl Rates {
+    /// Reads a table in JSON when it starts with `{`, and in CSV otherwise.
+    pub fn parse(text: &str) -> Result<Rates, String> {
+        match text.trim_start().starts_with('{') {
+            true => Rates::from_json(text),
+            false => Rates::from_csv(text),
+        }
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Rates, String> {
+        match std::fs::read_to_string(path.as_ref()) {
+            Err(error) => Err(format!("Cannot read {}: {}", path.as_ref().display(), error)),
+            Ok(text) => Rates::parse(&text),
+        }
+    }
+
+    /// Reads `{"base": "EUR", "timestamp": "2024-05-01", "rates": {"USD": 1.0823}}`, the timestamp
+    /// being named `date` by some providers. Rates may be written as strings to keep every digit.
+    pub fn from_json(text: &str) -> Result<Rates, String> {
+        let json: serde_json::Value = match serde_json::from_str(text) {
+            Err(error) => return Err(format!("Invalid rates: {}", error)),
+            Ok(json) => json,
+        };
+        let timestamp = match json.get("timestamp").or(json.get("date")) {
+            Some(serde_json::Value::String(timestamp)) => Some(timestamp.clone()),
+            Some(serde_json::Value::Number(timestamp)) => Some(timestamp.to_string()),
+            _ => None,
+        };
+        let mut rates = Rates::default();
+        if let Some(serde_json::Value::String(base)) = json.get("base") {
+            rates.insert(base, BigRational::from_integer(BigInt::from(1)), timestamp.clone());
+        }
+        let entries = match json.get("rates") {
+            Some(serde_json::Value::Object(entries)) => entries,
+            _ => return Err(String::from(r#"Invalid rates: missing "rates" object"#)),
+        };
+        for (code, value) in entries {
+            let text = match value {
+                serde_json::Value::String(text) => text.clone(),
+                serde_json::Value::Number(number) => number.to_string(),
+                _ => return Err(format!(r#"Invalid rate for "{}""#, code)),
+            };
+            match Rates::parse_rate(&text) {
+                None => return Err(format!(r#"Invalid rate "{}" for "{}""#, text, code)),
+                Some(rate) => rates.insert(code, rate, timestamp.clone()),
+            }
+        }
+        Ok(rates)
+    }
+
+    /// Reads lines of `code,rate` or `code,rate,timestamp`, skipping a header line, empty lines and
+    /// comments starting with `#`.
+    pub fn from_csv(text: &str) -> Result<Rates, String> {
+        let mut rates = Rates::default();
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let fields: Vec<&str> = line.split(',').map(|field| field.trim().trim_matches('"')).collect();
+            match (fields.as_slice(), fields.get(1).and_then(|rate| Rates::parse_rate(rate))) {
+                ([code, _], Some(rate)) => rates.insert(code, rate, None),
+                ([code, _, timestamp], Some(rate)) => rates.insert(code, rate, Some(String::from(*timestamp))),
+                _ if index == 0 => continue,
+                _ => return Err(format!("Invalid rate on line {}: {}", index + 1, line)),
+            }
+        }
+        Ok(rates)
+    }
+
+    fn parse_rate(text: &str) -> Option<BigRational> {
+        Value::parse_rational(text).filter(|rate| !rate.is_zero())
+    }
+
+    pub fn insert(&mut self, code: &str, value: BigRational, timestamp: Option<String>) {
+        self.rates.insert(String::from(code), Rate { value, timestamp });
+    }
+
+    /// Converts an exact amount, giving the timestamp of the oldest rate used.
+    pub fn convert(&self, amount: &BigRational, from: &Currency, to: &Currency) -> Option<(BigRational, Option<String>)> {
+        if from == to {
+            return Some((amount.clone(), None));
+        }
+        let (source, target) = (self.rates.get(from.code)?, self.rates.get(to.code)?);
+        let timestamp = match (&source.timestamp, &target.timestamp) {
+            (Some(a), Some(b)) => Some(a.min(b).clone()),
+            (a, b) => a.clone().or(b.clone()),
+        };
+        Some((amount * &target.value / &source.value, timestamp))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn amount(text: &str) -> BigRational {
+        Value::parse_rational(text).unwrap()
+    }
+
+    #[test]
+    fn reads_rates_in_json_and_csv() {
+        let json = Rates::parse(r#"{"base": "EUR", "date": "2024-05-01", "rates": {"USD": "1.0823", "JPY": 168}}"#).unwrap();
+        let (eur, usd, jpy) = (get_currency("EUR").unwrap(), get_currency("$").unwrap(), get_currency("JPY").unwrap());
+        assert_eq!(json.convert(&amount("10"), eur, usd), Some((amount("10.823"), Some(String::from("2024-05-01")))));
+        assert_eq!(json.convert(&amount("168"), jpy, eur).map(|(amount, _)| amount), Some(amount("1")));
+        let csv = Rates::parse("code,rate\n# comment\nEUR,1\nUSD,1.0823,2024-05-02\n").unwrap();
+        assert_eq!(csv.convert(&amount("10"), eur, usd), Some((amount("10.823"), Some(String::from("2024-05-02")))));
+        assert!(csv.convert(&amount("1"), eur, jpy).is_none());
+        assert!(Rates::parse("EUR,1\nUSD,zero").is_err());
+        assert!(Rates::parse(r#"{"base": "EUR"}"#).is_err());
+    }
+
+    #[test]
+    fn writes_amounts_in_minor_units() {
+        let money = |amount: BigRational, code: &str| Money { amount, currency: get_currency(code).unwrap(), timestamp: None };
+        assert_eq!(money(amount("12.5"), "USD").to_string(), "12.50 USD");
+        assert_eq!(money(-amount("0.05"), "EUR").to_string(), "-0.05 EUR");
+        assert_eq!(money(amount("1234.5"), "JPY").to_string(), "1235 JPY");
+        assert_eq!(money(amount("1.2345"), "KWD").get_amount(), "1.235");
+    }
+
+    #[test]
+    fn rejects_rates_with_huge_exponents() {
+        let error = Rates::parse("code,rate\nUSD,1e100000000\n").unwrap_err();
+        assert_eq!(error, "Invalid rate on line 2: USD,1e100000000");
+        let error = Rates::parse(r#"{"base": "EUR", "rates": {"USD": "1e-100000000"}}"#).unwrap_err();
+        assert_eq!(error, r#"Invalid rate "1e-100000000" for "USD""#);
+    }
+}
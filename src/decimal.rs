@@ -0,0 +1,533 @@
+use std::cmp::Ordering;
+use std::fmt::{Display, Formatter};
+use num_bigint::BigInt;
+use num_integer::Integer;
+use num_rational::BigRational;
+use num_traits::{One, Signed, Zero};
+
+/// Digits computed beyond the precision before rounding the result of a function.
+const GUARD_DIGITS: u64 = 10;
+/// How many times a function is recomputed with more digits when its result lost significance.
+const MAX_RETRIES: usize = 8;
+/// Largest argument of `exp`, whose result would otherwise have too many digits to compute.
+const MAX_EXPONENT: i64 = 100_000;
+/// Largest power of ten scaling the digits of a product either way, so that its exponent can't
+/// overflow and `to_rational` doesn't expand it into a huge integer.
+const MAX_SCALE: i64 = 100_000;
+
+/**************************************************************************************************/
+/*                                            CONTEXT                                             */
+/**************************************************************************************************/
+
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub enum Rounding {
+    /// To the nearest, ties to the even neighbour, `2.5 -> 2`.
+    #[default]
+    HalfEven,
+    /// To the nearest, ties away from zero, `2.5 -> 3`.
+    HalfUp,
+    /// Towards zero.
+    Down,
+    /// Away from zero.
+    Up,
+    /// Towards positive infinity.
+    Ceiling,
+    /// Towards negative infinity.
+    Floor,
+}
+
+impl Rounding {
+    pub fn parse(name: &str) -> Option<Rounding> {
+        match name {
+            "half-even" => Some(Rounding::HalfEven),
+            "half-up" => Some(Rounding::HalfUp),
+            "down" => Some(Rounding::Down),
+            "up" => Some(Rounding::Up),
+            "ceiling" => Some(Rounding::Ceiling),
+            "floor" => Some(Rounding::Floor),
+            _ => None,
+        }
+    }
+
+    /// Tells whether the truncated magnitude `quotient` must be incremented, given the dropped
+    /// fraction `remainder / divisor`.
+    fn increments(&self, quotient: &BigInt, remainder: &BigInt, divisor: &BigInt, negative: bool) -> bool {
+        if remainder.is_zero() {
+            return false;
+        }
+        let half = (remainder * BigInt::from(2)).cmp(divisor);
+        match self {
+            Rounding::HalfEven => half == Ordering::Greater || (half == Ordering::Equal && quotient.is_odd()),
+            Rounding::HalfUp => half != Ordering::Less,
+            Rounding::Down => false,
+            Rounding::Up => true,
+            Rounding::Ceiling => !negative,
+            Rounding::Floor => negative,
+        }
+    }
+
+    /// Rounds an exact value to a number of decimal places, as money to its minor unit.
+    pub fn round(&self, value: &BigRational, places: u32) -> BigRational {
+        let scale = power_of_ten(places as i64);
+        let scaled = value * &scale;
+        let (mut quotient, remainder) = scaled.numer().abs().div_rem(scaled.denom());
+        let negative = value.is_negative();
+        if self.increments(&quotient, &remainder, scaled.denom(), negative) {
+            quotient += 1;
+        }
+        BigRational::new(if negative { -quotient } else { quotient }, scale)
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Context {
+    /// Number of significant digits kept by every operation.
+    pub precision: u64,
+    pub rounding: Rounding,
+}
+
+impl Default for Context {
+    fn default() -> Self {
+        Context { precision: 50, rounding: Rounding::default() }
+    }
+}
+
+/**************************************************************************************************/
+/*                                            DECIMAL                                             */
+/**************************************************************************************************/
+
+/// The number `digits * 10^exponent`, without trailing zeros in `digits`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Decimal {
+    pub digits: BigInt,
+    pub exponent: i64,
+}
+
+impl Decimal {
+    pub fn zero() -> Decimal {
+        Decimal { digits: BigInt::zero(), exponent: 0 }
+    }
+
+    /// Rounds an exact value to the precision of the context.
+    pub fn from_rational(value: &BigRational, context: &Context) -> Decimal {
+        if value.is_zero() {
+            return Decimal::zero();
+        }
+        let negative = value.is_negative();
+        let numerator = value.numer().abs();
+        let denominator = value.denom();
+        let precision = context.precision.max(1) as i64;
+
+        // Picks the exponent giving a quotient of exactly `precision` digits, the first guess being
+        // off by one at most.
+        let mut exponent = count_digits(&numerator) - count_digits(denominator) - precision + 1;
+        let (mut quotient, mut remainder, mut divisor);
+        loop {
+            (divisor, quotient, remainder) = match exponent {
+                exponent if exponent <= 0 => {
+                    let (quotient, remainder) = (&numerator * power_of_ten(-exponent)).div_rem(denominator);
+                    (denominator.clone(), quotient, remainder)
+                },
+                exponent => {
+                    let divisor = denominator * power_of_ten(exponent);
+                    let (quotient, remainder) = numerator.div_rem(&divisor);
+                    (divisor, quotient, remainder)
+                },
+            };
+            match count_digits(&quotient).cmp(&precision) {
+                Ordering::Less => exponent -= 1,
+                Ordering::Greater => exponent += 1,
+                Ordering::Equal => break,
+            }
+        }
+
+        if context.rounding.increments(&quotient, &remainder, &divisor, negative) {
+            quotient += 1;
+        }
+        let digits = if negative { -quotient } else { quotient };
+        Decimal { digits, exponent }.normalize()
+    }
+
+    /// Expands the exponent, which is bounded by `MAX_SCALE` or by the size of the rational the
+    /// decimal was rounded from.
+    pub fn to_rational(&self) -> BigRational {
+        if self.exponent < 0 {
+            BigRational::new(self.digits.clone(), power_of_ten(-self.exponent))
+        } else {
+            BigRational::from_integer(&self.digits * power_of_ten(self.exponent))
+        }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.digits.is_zero()
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.digits.is_negative()
+    }
+
+    fn normalize(mut self) -> Decimal {
+        if self.digits.is_zero() {
+            return Decimal::zero();
+        }
+        let ten = BigInt::from(10);
+        loop {
+            let (quotient, remainder) = self.digits.div_rem(&ten);
+            if !remainder.is_zero() {
+                return self;
+            }
+            self.digits = quotient;
+            self.exponent += 1;
+        }
+    }
+
+    /// Multiplies without going through rationals, which would expand large exponents, giving
+    /// `None` when the product is scaled beyond `MAX_SCALE`.
+    fn multiply(&self, other: &Decimal, context: &Context) -> Option<Decimal> {
+        let product = BigRational::from_integer(&self.digits * &other.digits);
+        match Decimal::from_rational(&product, context) {
+            product if product.is_zero() => Some(product),
+            product => {
+                let exponent = product.exponent.checked_add(self.exponent)?.checked_add(other.exponent)?;
+                (exponent.abs() <= MAX_SCALE).then_some(Decimal { exponent, ..product })
+            },
+        }
+    }
+
+    /// Raises to an integral power by squaring, keeping guard digits on the intermediate results.
+    /// Gives `None` for a negative power of zero and for a result out of range.
+    pub fn powi(&self, exponent: &BigInt, context: &Context) -> Option<Decimal> {
+        if exponent.is_negative() {
+            if self.is_zero() {
+                return None;
+            }
+            let inverse = BigRational::one() / self.to_rational();
+            return Decimal::from_rational(&inverse, &context.guarded()).powi(&-exponent, context);
+        }
+        let working = context.guarded();
+        let mut result = Decimal { digits: BigInt::one(), exponent: 0 };
+        let mut base = self.clone();
+        let mut exponent = exponent.clone();
+        while !exponent.is_zero() {
+            if exponent.is_odd() {
+                result = result.multiply(&base, &working)?;
+            }
+            exponent >>= 1;
+            if !exponent.is_zero() {
+                base = base.multiply(&base, &working)?;
+            }
+        }
+        result.multiply(&Decimal { digits: BigInt::one(), exponent: 0 }, context)
+    }
+
+    /// Raises to any power, through `exp(exponent * ln(self))` when the exponent isn't integral.
+    pub fn pow(&self, exponent: &Decimal, context: &Context) -> Option<Decimal> {
+        let power = exponent.to_rational();
+        if power.is_integer() {
+            return self.powi(&power.to_integer(), context);
+        }
+        if self.is_zero() {
+            return if exponent.is_negative() { None } else { Some(Decimal::zero()) };
+        }
+        let working = context.guarded();
+        let logarithm = self.ln(&working)?;
+        exponent.multiply(&logarithm, &working)?.exp(context)
+    }
+
+    pub fn sqrt(&self, context: &Context) -> Option<Decimal> {
+        if self.is_negative() {
+            return None;
+        }
+        if self.is_zero() {
+            return Some(Decimal::zero());
+        }
+        let value = self.to_rational();
+        // With `digits` fractional digits, the root of `value * 10^(2 * digits)` is the fixed point root.
+        compute(context, magnitude(&value) / 2, |digits| {
+            Some((value.clone() * BigRational::from_integer(power_of_ten(2 * digits as i64))).to_integer().sqrt())
+        })
+    }
+
+    pub fn exp(&self, context: &Context) -> Option<Decimal> {
+        let value = self.to_rational();
+        if value.abs() > BigRational::from_integer(BigInt::from(MAX_EXPONENT)) {
+            return None;
+        }
+        if value.is_negative() {
+            let inverse = Decimal::from_rational(&-value, &context.guarded()).exp(&context.guarded())?;
+            return Some(Decimal::from_rational(&(BigRational::one() / inverse.to_rational()), context));
+        }
+        compute(context, 0, |digits| Some(exp_fixed(&value, digits)))
+    }
+
+    pub fn ln(&self, context: &Context) -> Option<Decimal> {
+        if self.is_negative() || self.is_zero() {
+            return None;
+        }
+        let value = self.to_rational();
+        if value.is_one() {
+            return Some(Decimal::zero());
+        }
+        // `value = mantissa * 2^shift` with a mantissa close to one, and
+        // `ln(mantissa) = 2 * atanh((mantissa - 1) / (mantissa + 1))`.
+        let shift = value.numer().bits() as i64 - value.denom().bits() as i64;
+        let mantissa = if shift < 0 {
+            &value * BigRational::from_integer(BigInt::one() << (-shift) as usize)
+        } else {
+            &value / BigRational::from_integer(BigInt::one() << shift as usize)
+        };
+        let ratio = (&mantissa - BigRational::one()) / (&mantissa + BigRational::one());
+        compute(context, 0, |digits| {
+            // `ln(2) = 2 * atanh(1/3)`.
+            let third = BigRational::new(BigInt::one(), BigInt::from(3));
+            Some(atanh_fixed(&ratio, digits) * 2 + atanh_fixed(&third, digits) * 2 * shift)
+        })
+    }
+
+    pub fn sin(&self, context: &Context) -> Option<Decimal> {
+        if self.is_zero() {
+            return Some(Decimal::zero());
+        }
+        let value = self.to_rational();
+        compute(context, 0, |digits| Some(sin_cos_fixed(&value, digits, true)))
+    }
+
+    pub fn cos(&self, context: &Context) -> Option<Decimal> {
+        let value = self.to_rational();
+        compute(context, 0, |digits| Some(sin_cos_fixed(&value, digits, false)))
+    }
+
+    pub fn tan(&self, context: &Context) -> Option<Decimal> {
+        let working = context.guarded();
+        let cos = self.cos(&working)?;
+        if cos.is_zero() {
+            return None;
+        }
+        let sin = self.sin(&working)?;
+        Some(Decimal::from_rational(&(sin.to_rational() / cos.to_rational()), context))
+    }
+
+    pub fn pi(context: &Context) -> Decimal {
+        compute(context, 0, |digits| Some(pi_fixed(digits))).unwrap()
+    }
+
+    pub fn tau(context: &Context) -> Decimal {
+        compute(context, 0, |digits| Some(pi_fixed(digits) * 2)).unwrap()
+    }
+
+    pub fn e(context: &Context) -> Decimal {
+        compute(context, 0, |digits| Some(exp_fixed(&BigRational::one(), digits))).unwrap()
+    }
+}
+
+impl Context {
+    fn guarded(&self) -> Context {
+        Context { precision: self.precision + GUARD_DIGITS, rounding: Rounding::HalfEven }
+    }
+}
+
+impl Display for Decimal {
+    /// Writes plain digits, or the scientific notation outside of `[1e-7, 1e21)` like `Value::Number`.
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let sign = if self.is_negative() { "-" } else { "" };
+        let digits = self.digits.abs().to_string();
+        let adjusted = digits.len() as i64 - 1 + self.exponent;
+        if self.is_zero() {
+            write!(f, "0")
+        } else if !(-7..21).contains(&adjusted) {
+            match digits.split_at(1) {
+                (first, "") => write!(f, "{}{}e{}", sign, first, adjusted),
+                (first, rest) => write!(f, "{}{}.{}e{}", sign, first, rest, adjusted),
+            }
+        } else if self.exponent >= 0 {
+            write!(f, "{}{}{}", sign, digits, "0".repeat(self.exponent as usize))
+        } else {
+            let point = digits.len() as i64 + self.exponent;
+            if point > 0 {
+                let (whole, fraction) = digits.split_at(point as usize);
+                write!(f, "{}{}.{}", sign, whole, fraction)
+            } else {
+                write!(f, "{}0.{}{}", sign, "0".repeat(-point as usize), digits)
+            }
+        }
+    }
+}
+
+/**************************************************************************************************/
+/*                                          FIXED POINT                                           */
+/**************************************************************************************************/
+
+// The series below work on integers scaled by `10^digits`.
+
+fn power_of_ten(exponent: i64) -> BigInt {
+    num_traits::pow(BigInt::from(10), exponent as usize)
+}
+
+fn count_digits(value: &BigInt) -> i64 {
+    if value.is_zero() { 0 } else { value.abs().to_string().len() as i64 }
+}
+
+/// Approximates the base ten logarithm of a positive rational.
+fn magnitude(value: &BigRational) -> i64 {
+    count_digits(value.numer()) - count_digits(value.denom())
+}
+
+fn to_fixed(value: &BigRational, digits: u64) -> BigInt {
+    (value * BigRational::from_integer(power_of_ten(digits as i64))).to_integer()
+}
+
+/// Evaluates a fixed point function, adding digits until the result has enough significant ones.
+/// `scale` is the expected magnitude of the result, as a power of ten.
+fn compute<F>(context: &Context, scale: i64, function: F) -> Option<Decimal>
+where
+    F: Fn(u64) -> Option<BigInt>,
+{
+    let wanted = (context.precision + GUARD_DIGITS) as i64;
+    let mut digits = (wanted - scale).max(GUARD_DIGITS as i64) as u64;
+    let mut result = function(digits)?;
+    for _ in 0..MAX_RETRIES {
+        let missing = wanted - count_digits(&result);
+        if missing <= 0 {
+            break;
+        }
+        digits += missing as u64;
+        result = function(digits)?;
+    }
+    let value = BigRational::new(result, power_of_ten(digits as i64));
+    Some(Decimal::from_rational(&value, context))
+}
+
+/// Sums `exp(value)` for a non-negative value, halving it first so that the series converges fast.
+fn exp_fixed(value: &BigRational, digits: u64) -> BigInt {
+    let halvings = value.to_integer().bits() + 1;
+    // Each squaring doubles the relative error, so a few more digits are kept.
+    let working = digits + halvings / 3 + GUARD_DIGITS;
+    let scale = power_of_ten(working as i64);
+    let reduced = to_fixed(&(value / BigRational::from_integer(BigInt::one() << halvings as usize)), working);
+
+    let mut sum = scale.clone();
+    let mut term = scale.clone();
+    let mut n = BigInt::zero();
+    loop {
+        n += 1;
+        term = &term * &reduced / (&scale * &n);
+        if term.is_zero() {
+            break;
+        }
+        sum += &term;
+    }
+    for _ in 0..halvings {
+        sum = &sum * &sum / &scale;
+    }
+    sum / power_of_ten((working - digits) as i64)
+}
+
+/// Sums `atanh(value) = value + value^3 / 3 + value^5 / 5 + ...` for `|value| < 1`.
+fn atanh_fixed(value: &BigRational, digits: u64) -> BigInt {
+    let working = digits + GUARD_DIGITS;
+    let scale = power_of_ten(working as i64);
+    let square = to_fixed(&(value * value), working);
+
+    let mut power = to_fixed(value, working);
+    let mut sum = BigInt::zero();
+    let mut n = BigInt::one();
+    while !power.is_zero() {
+        sum += &power / &n;
+        power = &power * &square / &scale;
+        n += 2;
+    }
+    sum / power_of_ten(GUARD_DIGITS as i64)
+}
+
+/// Computes pi with Machin's formula, `pi = 16 * atan(1/5) - 4 * atan(1/239)`.
+fn pi_fixed(digits: u64) -> BigInt {
+    let atan = |inverse: i64| {
+        let working = digits + GUARD_DIGITS;
+        let square = BigInt::from(inverse * inverse);
+        let mut power = power_of_ten(working as i64) / inverse;
+        let mut sum = BigInt::zero();
+        let mut n = BigInt::one();
+        let mut sign = 1;
+        while !power.is_zero() {
+            sum += &power / &n * sign;
+            power /= &square;
+            n += 2;
+            sign = -sign;
+        }
+        sum
+    };
+    (atan(5) * 16 - atan(239) * 4) / power_of_ten(GUARD_DIGITS as i64)
+}
+
+/// Sums the Taylor series of the sine or the cosine, after reducing the angle modulo two pi.
+fn sin_cos_fixed(value: &BigRational, digits: u64, sine: bool) -> BigInt {
+    // Reducing a large angle needs as many more digits of pi as it has integral digits.
+    let working = digits + GUARD_DIGITS + count_digits(&value.to_integer()) as u64;
+    let scale = power_of_ten(working as i64);
+    let pi = pi_fixed(working);
+    let tau = &pi * 2;
+
+    let mut angle = to_fixed(value, working).mod_floor(&tau);
+    if angle > pi {
+        angle -= &tau;
+    }
+    let square = &angle * &angle / &scale;
+
+    let mut term = if sine { angle } else { scale.clone() };
+    let mut sum = BigInt::zero();
+    let mut n = if sine { BigInt::one() } else { BigInt::zero() };
+    while !term.is_zero() {
+        sum += &term;
+        term = -(&term * &square) / (&scale * (&n + 1) * (&n + 2));
+        n += 2;
+    }
+    sum / power_of_ten((working - digits) as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decimal(numerator: i64, denominator: i64, precision: u64, rounding: Rounding) -> String {
+        let value = BigRational::new(BigInt::from(numerator), BigInt::from(denominator));
+        Decimal::from_rational(&value, &Context { precision, rounding }).to_string()
+    }
+
+    #[test]
+    fn rounds_to_the_precision_of_the_context() {
+        assert_eq!(decimal(1, 3, 5, Rounding::HalfEven), "0.33333");
+        assert_eq!(decimal(2, 3, 5, Rounding::HalfEven), "0.66667");
+        assert_eq!(decimal(2, 3, 5, Rounding::Down), "0.66666");
+        assert_eq!(decimal(25, 10, 1, Rounding::HalfEven), "2");
+        assert_eq!(decimal(25, 10, 1, Rounding::HalfUp), "3");
+        assert_eq!(decimal(-21, 10, 1, Rounding::Floor), "-3");
+        assert_eq!(decimal(-21, 10, 1, Rounding::Ceiling), "-2");
+        assert_eq!(decimal(1, 1, 50, Rounding::HalfEven), "1");
+        assert_eq!(Rounding::parse("half-up"), Some(Rounding::HalfUp));
+        assert_eq!(Rounding::parse("nearest"), None);
+    }
+
+    #[test]
+    fn computes_functions_to_the_precision_of_the_context() {
+        let context = Context { precision: 20, rounding: Rounding::HalfEven };
+        let two = Decimal::from_rational(&BigRational::from_integer(BigInt::from(2)), &context);
+        assert_eq!(two.sqrt(&context).unwrap().to_string(), "1.4142135623730950488");
+        assert_eq!(Decimal::pi(&context).to_string(), "3.1415926535897932385");
+        assert_eq!(Decimal::e(&context).to_string(), "2.7182818284590452354");
+        assert!(two.powi(&BigInt::from(-1), &context).is_some_and(|half| half.to_string() == "0.5"));
+        assert!(Decimal::zero().ln(&context).is_none());
+    }
+
+    #[test]
+    fn keeps_exponents_of_powers_in_range() {
+        let context = Context::default();
+        let ten = Decimal::from_rational(&BigRational::from_integer(BigInt::from(10)), &context);
+        let huge = BigInt::from(10).pow(30);
+        assert!(ten.powi(&huge, &context).is_none());
+        assert!(ten.powi(&-huge, &context).is_none());
+        assert_eq!(ten.powi(&BigInt::from(MAX_SCALE), &context).unwrap().exponent, MAX_SCALE);
+        assert!(ten.powi(&BigInt::from(MAX_SCALE + 1), &context).is_none());
+        let large = Decimal { digits: BigInt::one(), exponent: i64::MAX };
+        assert!(large.multiply(&large, &context).is_none());
+    }
+}
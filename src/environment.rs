@@ -0,0 +1,117 @@
+use std::collections::HashMap;
+use crate::model::*;
+use crate::value::*;
+
+/**************************************************************************************************/
+/*                                          DEFINITION                                            */
+/**************************************************************************************************/
+
+/// A user-defined function, along with the local scopes that were visible where it was defined.
+/// Its body owns its tokens, since it outlives the source it was read from.
+#[derive(Debug, Clone)]
+pub struct Definition {
+    pub parameters: Vec<String>,
+    pub body: NodeItem<'static>,
+    closure: Vec<Scope>,
+}
+
+/**************************************************************************************************/
+/*                                          ENVIRONMENT                                           */
+/**************************************************************************************************/
+
+type Scope = HashMap<String, Value>;
+
+/// Variables live in a stack of scopes: the first one is the global scope, which persists between
+/// evaluations. Assignments always bind in the innermost scope and lookups walk outward, so a
+/// binding shadows any outer one with the same name, including the built-in constants.
+///
+/// Functions are scoped lexically: a call only sees its parameters, the scopes captured where the
+/// function was defined and the global scope, never the local variables of its caller.
+#[derive(Debug, Clone)]
+pub struct Environment {
+    scopes: Vec<Scope>,
+    functions: HashMap<String, Definition>,
+}
+
+impl Default for Environment {
+    fn default() -> Self {
+        Environment { scopes: vec![HashMap::new()], functions: HashMap::new() }
+    }
+}
+
+impl Environment {
+    pub fn get(&self, name: &str) -> Option<Value> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(name)).cloned()
+    }
+
+    pub fn set(&mut self, name: &str, value: Value) {
+        self.scopes.last_mut().unwrap().insert(name.to_string(), value);
+    }
+
+    pub fn get_function(&self, name: &str) -> Option<Definition> {
+        self.functions.get(name).cloned()
+    }
+
+    pub fn set_function(&mut self, name: &str, parameters: Vec<String>, body: NodeItem<'static>) {
+        let closure = self.scopes[1..].to_vec();
+        self.functions.insert(name.to_string(), Definition { parameters, body, closure });
+    }
+
+    /// Replaces the local scopes by the ones of a call to `definition`, returning the caller's
+    /// scopes so that they can be given back to `leave` once the call is over.
+    pub fn enter(&mut self, definition: &Definition, arguments: Vec<Value>) -> Vec<Scope> {
+        let mut scope = HashMap::new();
+        for (name, value) in definition.parameters.iter().zip(arguments) {
+            scope.insert(name.clone(), value);
+        }
+
+        let mut locals = definition.closure.clone();
+        locals.push(scope);
+        self.scopes.splice(1.., locals).collect()
+    }
+
+    pub fn leave(&mut self, locals: Vec<Scope>) {
+        self.scopes.truncate(1);
+        self.scopes.extend(locals);
+    }
+
+    pub fn clear(&mut self) {
+        self.scopes = vec![HashMap::new()];
+        self.functions.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_variables_until_cleared() {
+        let mut environment = Environment::default();
+        assert!(environment.get("x").is_none());
+        environment.set("x", Value::Number(1.0));
+        environment.set("x", Value::Number(2.0));
+        assert!(matches!(environment.get("x"), Some(Value::Number(value)) if value == 2.0));
+        environment.clear();
+        assert!(environment.get("x").is_none());
+    }
+
+    #[test]
+    fn hides_the_locals_of_callers() {
+        let mut environment = Environment::default();
+        environment.set("g", Value::Number(1.0));
+        let body = either::Left(crate::lexer::Lexer::from("x").read().remove(0));
+        environment.set_function("f", vec![String::from("x")], body);
+        let definition = environment.get_function("f").unwrap();
+        let caller = environment.enter(&definition, vec![Value::Number(2.0)]);
+        environment.set("y", Value::Number(3.0));
+        let inner = environment.enter(&definition, vec![Value::Number(4.0)]);
+        assert!(matches!(environment.get("x"), Some(Value::Number(value)) if value == 4.0));
+        assert!(environment.get("y").is_none());
+        assert!(environment.get("g").is_some());
+        environment.leave(inner);
+        assert!(matches!(environment.get("y"), Some(Value::Number(value)) if value == 3.0));
+        environment.leave(caller);
+        assert!(environment.get("x").is_none());
+    }
+}
@@ -0,0 +1,2447 @@
+use crate::calendar::*;
+use crate::currency::*;
+use crate::decimal::*;
+use crate::environment::*;
+use crate::library::*;
+use crate::matrix::*;
+use crate::model::*;
+use crate::unit::*;
+use crate::value::*;
+use chrono::TimeDelta;
+use either::*;
+use num_bigint::BigInt;
+use num_complex::Complex64;
+use num_integer::Integer;
+use num_rational::BigRational;
+use num_traits::{FromPrimitive, One, Signed, ToPrimitive, Zero};
+use std::borrow::Cow;
+
+/// Largest integer whose factorial is computed.
+const MAX_FACTORIAL: u64 = 10_000;
+/// Size of the largest exact power or shift computed, larger ones giving a float in the float and
+/// rational modes and being out of range in the integer modes.
+const MAX_POWER_BITS: u64 = 1 << 24;
+/// Largest integer from which every smaller one is exactly a float.
+const MAX_SAFE_INTEGER: u64 = 1 << 53;
+/// Nodes evaluated inside one another at most, calls of user-defined functions included, which
+/// keeps the evaluation within the 1 MB stack of WebAssembly. Debug builds have larger frames.
+const MAX_FRAMES: usize = if cfg!(debug_assertions) { 96 } else { 416 };
+
+macro_rules! current_method {
+    () => {{
+        fn f() {}
+        fn type_name_of<T>(_: T) -> &'static str {
+            std::any::type_name::<T>()
+        }
+        let name = type_name_of(f);
+        &name[..name.len() - 3]
+    }}
+}
+
+/**************************************************************************************************/
+/*                                           EVALUATOR                                            */
+/**************************************************************************************************/
+
+type EvaluatorResult = Result<Value, Exception>;
+
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub enum Remainder {
+    /// Sign follows the dividend, `-7 % 3 = -1`.
+    #[default]
+    Truncated,
+    /// Sign follows the divisor, `-7 % 3 = 2`.
+    Floored,
+}
+
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub enum Overflow {
+    Wrapping,
+    Saturating,
+    #[default]
+    Error,
+}
+
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub enum Mode {
+    #[default]
+    Float,
+    /// Programmer mode: literals are exact integers, `/` truncates and `^` is the exclusive or.
+    Integer,
+    /// Programmer mode where unsuffixed literals have the given fixed width.
+    Fixed(IntegerType),
+    /// Exact mode: decimal literals are read as fractions so that `0.1 + 0.2 == 0.3`.
+    Rational,
+    /// Arbitrary-precision mode, rounding every result to the precision of the decimal context.
+    Decimal,
+}
+
+pub struct Evaluator {
+    environment: Environment,
+    mode: Mode,
+    overflow: Overflow,
+    context: Context,
+    remainder: Remainder,
+    rates: Rates,
+    depth: usize,
+    max_depth: usize,
+    frames: usize,
+}
+
+impl Default for Evaluator {
+    fn default() -> Self {
+        Evaluator {
+            environment: Environment::default(),
+            mode: Mode::default(),
+            overflow: Overflow::default(),
+            context: Context::default(),
+            remainder: Remainder::default(),
+            rates: Rates::default(),
+            depth: 0,
+            max_depth: 100,
+            frames: 0,
+        }
+    }
+}
+
+impl Evaluator {
+    pub fn get_environment(&mut self) -> &mut Environment {
+        &mut self.environment
+    }
+
+    pub fn get_mode(&self) -> Mode {
+        self.mode
+    }
+
+    pub fn set_mode(&mut self, mode: Mode) {
+        self.mode = mode;
+    }
+
+    /// Chooses what happens when fixed-width integer arithmetic leaves the range of its type.
+    pub fn set_overflow(&mut self, overflow: Overflow) {
+        self.overflow = overflow;
+    }
+
+    /// Sets the number of significant digits kept in the decimal mode.
+    pub fn set_precision(&mut self, precision: u64) {
+        self.context.precision = precision.max(1);
+    }
+
+    pub fn set_rounding(&mut self, rounding: Rounding) {
+        self.context.rounding = rounding;
+    }
+
+    pub fn set_remainder(&mut self, remainder: Remainder) {
+        self.remainder = remainder;
+    }
+
+    /// Replaces the exchange rates used to convert between currencies.
+    pub fn set_rates(&mut self, rates: Rates) {
+        self.rates = rates;
+    }
+
+    /// Bounds the nesting of user-defined function calls, so that a runaway recursion is reported
+    /// before it overflows the stack. Every call takes a frame at least, so the bound is capped by
+    /// `MAX_FRAMES`, and a recursion through deep bodies stops before reaching it.
+    pub fn set_max_depth(&mut self, max_depth: usize) {
+        self.max_depth = max_depth.min(MAX_FRAMES);
+    }
+
+    /// Evaluates a concrete syntax tree from the parser, walking it in place.
+    pub fn evaluate(&mut self, node: &Node) -> EvaluatorResult {
+        self.depth = 0;
+        self.frames = 0;
+        match self.evaluate_node(node) {
+            Err(error) => Err(Exception::relay(error, current_method!())),
+            Ok(value) => Ok(value),
+        }
+    }
+
+    fn evaluate_item(&mut self, item: &NodeItem) -> EvaluatorResult {
+        match item {
+            Left(token) => self.evaluate_token(token),
+            Right(node) => self.evaluate_node(node),
+        }
+    }
+
+    fn evaluate_node(&mut self, node: &Node) -> EvaluatorResult {
+        if self.frames >= MAX_FRAMES {
+            return Err(Exception::create(Error::NestingLimit(MAX_FRAMES, node.get_start()), current_method!()));
+        }
+        self.frames += 1;
+        let result = self.evaluate_kind(node);
+        self.frames -= 1;
+        result
+    }
+
+    fn evaluate_kind(&mut self, node: &Node) -> EvaluatorResult {
+        match node.get_type().as_str() {
+            "Calcul" => match node.children().first() {
+                None => Err(Exception::create(Error::EOF, current_method!())),
+                Some(item) => match self.evaluate_item(item) {
+                    Err(error) => Err(Exception::relay(error, current_method!())),
+                    Ok(value) => Ok(value),
+                },
+            },
+            "Group" => match node.children().get(1) {
+                None => Err(Exception::create(Error::UnexpectedItem(Right(node.clone().into_owned())), current_method!())),
+                Some(item) => self.evaluate_item(item),
+            },
+            kind if Evaluator::is_chained(kind) => self.evaluate_chain(node),
+            "Negate" | "Identity" | "BitNot" => self.evaluate_unary(node),
+            "Constant" => self.evaluate_constant(node),
+            "Call" => self.evaluate_call(node),
+            "Assign" => self.evaluate_assign(node),
+            "Define" => self.evaluate_define(node),
+            "Not" => self.evaluate_not(node),
+            "Conditional" => self.evaluate_conditional(node),
+            "Unit" => self.evaluate_quantity(node),
+            "Compound" => self.evaluate_compound(node),
+            "List" => self.evaluate_list(node),
+            _ => Err(Exception::create(Error::UnexpectedItem(Right(node.clone().into_owned())), current_method!())),
+        }
+    }
+
+    fn is_binary(kind: &str) -> bool {
+        matches!(
+            kind,
+            "Add" | "Subtract" | "Multiply" | "Divide" | "Modulo" | "Power" | "ElementMultiply" | "ElementDivide" | "Solve"
+                | "BitAnd" | "BitOr" | "BitXor" | "ShiftLeft" | "ShiftRight"
+        )
+    }
+
+    fn is_comparison(kind: &str) -> bool {
+        matches!(kind, "Equal" | "NotEqual" | "Less" | "LessEqual" | "Greater" | "GreaterEqual")
+    }
+
+    /// Tells whether the first item of the node is its left operand, as in the operations of a
+    /// left-associative chain and the postfix operators.
+    fn is_chained(kind: &str) -> bool {
+        Evaluator::is_binary(kind)
+            || Evaluator::is_comparison(kind)
+            || matches!(kind, "And" | "Or" | "Convert" | "Percent" | "Factorial")
+    }
+
+    /// Evaluates a left-associative chain such as `1 + 2 + … + n` or `a && b && … && z` in a loop
+    /// from its innermost operation outwards, so that the length of the chain doesn't add to the
+    /// stack.
+    fn evaluate_chain(&mut self, node: &Node) -> EvaluatorResult {
+        let mut chain = vec![node];
+        while let Some(Right(left)) = chain.last().unwrap().children().first() {
+            if !Evaluator::is_chained(&left.get_type()) {
+                break;
+            }
+            chain.push(left);
+        }
+
+        let mut a = match chain.last().unwrap().children().first() {
+            None => return Err(Exception::create(Error::UnexpectedItem(Right(node.clone().into_owned())), current_method!())),
+            Some(left) => match self.evaluate_item(left) {
+                Err(error) => return Err(Exception::relay(error, current_method!())),
+                Ok(value) => value,
+            },
+        };
+        while let Some(node) = chain.pop() {
+            a = match self.evaluate_link(node, a) {
+                Err(error) => return Err(Exception::relay(error, current_method!())),
+                Ok(value) => value,
+            };
+        }
+        Ok(a)
+    }
+
+    /// Applies an operation of a chain to the value of its left operand, evaluating the right one
+    /// unless `&&` or `||` short-circuits.
+    fn evaluate_link(&mut self, node: &Node, a: Value) -> EvaluatorResult {
+        let value = node.children();
+        let kind = node.get_type();
+        match kind.as_str() {
+            "Percent" | "Factorial" => return self.apply_unary(node, &value[0], a),
+            "Convert" => return self.evaluate_conversion(node, a),
+            _ => {},
+        }
+        let (left, right) = match (value.first(), value.get(2)) {
+            (Some(left), Some(right)) => (left, right),
+            _ => return Err(Exception::create(Error::UnexpectedItem(Right(node.clone().into_owned())), current_method!())),
+        };
+
+        if kind == "And" || kind == "Or" {
+            return match Evaluator::get_condition(a, left) {
+                Err(error) => Err(Exception::relay(error, current_method!())),
+                Ok(a) if a == (kind == "Or") => Ok(Value::Boolean(a)),
+                Ok(_) => match self.evaluate_condition(right) {
+                    Err(error) => Err(Exception::relay(error, current_method!())),
+                    Ok(b) => Ok(Value::Boolean(b)),
+                },
+            };
+        }
+
+        let b = match self.evaluate_item(right) {
+            Err(error) => return Err(Exception::relay(error, current_method!())),
+            Ok(value) => value,
+        };
+        if Evaluator::is_comparison(&kind) {
+            self.compare(node, a, b)
+        } else {
+            self.evaluate_operation(node, a, b)
+        }
+    }
+
+    /// Applies a binary operator to the values of its operands. Kept out of line, so that its large
+    /// frame is not on the stack while the operands are evaluated.
+    #[inline(never)]
+    fn evaluate_operation(&self, node: &Node, a: Value, b: Value) -> EvaluatorResult {
+        let value = node.children();
+        let (left, operator, right) = match (value.first(), value.get(1), value.get(2)) {
+            (Some(left), Some(Left(operator)), Some(right)) => (left, operator, right),
+            _ => return Err(Exception::create(Error::UnexpectedItem(Right(node.clone().into_owned())), current_method!())),
+        };
+
+        let kind = node.get_type();
+        let integer_type = match (&a, &b) {
+            (Value::Fixed(_, t), Value::Fixed(_, u)) if t != u => {
+                let position = for_both!(right, item => item.get_start());
+                return Err(Exception::create(Error::UnexpectedValue(u.to_string(), position), current_method!()));
+            },
+            (Value::Fixed(_, t), _) | (_, Value::Fixed(_, t)) => Some(*t),
+            _ => None,
+        };
+        if let Some(integer_type) = integer_type {
+            let x = match Evaluator::get_fixed(a, integer_type, left) {
+                Err(error) => return Err(Exception::relay(error, current_method!())),
+                Ok(integer) => integer,
+            };
+            let y = match Evaluator::get_fixed(b, integer_type, right) {
+                Err(error) => return Err(Exception::relay(error, current_method!())),
+                Ok(integer) => integer,
+            };
+            return match self.evaluate_fixed(&kind, x, y, integer_type, operator, right) {
+                Err(error) => Err(Exception::relay(error, current_method!())),
+                Ok(result) => Ok(Value::Fixed(result, integer_type)),
+            };
+        }
+
+        let array = |value: &Value| matches!(value, Value::Vector(_) | Value::Matrix(_));
+        if array(&a) || array(&b) || matches!(kind.as_str(), "ElementMultiply" | "ElementDivide" | "Solve") {
+            return match self.evaluate_matrix(node, a, b) {
+                Err(error) => Err(Exception::relay(error, current_method!())),
+                Ok(value) => Ok(value),
+            };
+        }
+
+        // Like on a desk calculator, `200 + 10%` adds ten percent of the left operand.
+        let relative = matches!(Evaluator::get_inner(right), Right(node) if node.get_type() == "Percent");
+
+        let temporal = |value: &Value| matches!(value, Value::Date(_) | Value::DateTime(_) | Value::Time(_) | Value::Duration(_));
+        if temporal(&a) || temporal(&b) {
+            return match self.evaluate_temporal(node, a, b) {
+                Err(error) => Err(Exception::relay(error, current_method!())),
+                Ok(value) => Ok(value),
+            };
+        }
+
+        if let (Value::Money(_), _) | (_, Value::Money(_)) = (&a, &b) {
+            return match self.evaluate_money(node, a, b, relative) {
+                Err(error) => Err(Exception::relay(error, current_method!())),
+                Ok(value) => Ok(value),
+            };
+        }
+
+        if let (Value::Quantity(_, _), _) | (_, Value::Quantity(_, _)) = (&a, &b) {
+            let (x, unit) = match Evaluator::get_quantity(a, left) {
+                Err(error) => return Err(Exception::relay(error, current_method!())),
+                Ok(quantity) => quantity,
+            };
+            let (y, other) = match Evaluator::get_quantity(b, right) {
+                Err(error) => return Err(Exception::relay(error, current_method!())),
+                Ok(quantity) => quantity,
+            };
+            return match self.evaluate_units(node, x, unit, y, other, relative) {
+                Err(error) => Err(Exception::relay(error, current_method!())),
+                Ok(value) => Ok(value),
+            };
+        }
+
+        if let (Value::Complex(_), _) | (_, Value::Complex(_)) = (&a, &b) {
+            let complex = if matches!(a, Value::Complex(_)) { left } else { right };
+            let x = match Evaluator::get_complex(a, left) {
+                Err(error) => return Err(Exception::relay(error, current_method!())),
+                Ok(complex) => complex,
+            };
+            let y = match Evaluator::get_complex(b, right) {
+                Err(error) => return Err(Exception::relay(error, current_method!())),
+                Ok(complex) => complex,
+            };
+            return match kind.as_str() {
+                "Add" => Ok(Value::from_complex(if relative { x + x * y } else { x + y })),
+                "Subtract" => Ok(Value::from_complex(if relative { x - x * y } else { x - y })),
+                "Multiply" => Ok(Value::from_complex(x * y)),
+                "Divide" if y.is_zero() => {
+                    let position = for_both!(right, item => item.get_start());
+                    Err(Exception::create(Error::DivisionByZero(position), current_method!()))
+                },
+                "Divide" => Ok(Value::from_complex(x / y)),
+                // Integral powers are multiplied out, so that `i^2` is exactly `-1`.
+                "Power" if y.im == 0.0 && y.re.fract() == 0.0 && y.re.abs() <= i32::MAX as f64 => {
+                    Ok(Value::from_complex(x.powi(y.re as i32)))
+                },
+                "Power" if x.is_zero() => Ok(Value::Number(0.0)),
+                "Power" => Ok(Value::from_complex(x.powc(y))),
+                _ => {
+                    let position = for_both!(complex, item => item.get_start());
+                    Err(Exception::create(Error::UnexpectedValue(String::from("Complex"), position), current_method!()))
+                },
+            };
+        }
+
+        let decimal = matches!((&a, &b), (Value::Decimal(_), _) | (_, Value::Decimal(_)));
+        if let (true, Some(x), Some(y)) = (decimal, Evaluator::get_rational(&a), Evaluator::get_rational(&b)) {
+            let result = match kind.as_str() {
+                "Add" if relative => Some(&x + &x * y),
+                "Add" => Some(x + y),
+                "Subtract" if relative => Some(&x - &x * y),
+                "Subtract" => Some(x - y),
+                "Multiply" => Some(x * y),
+                "Divide" | "Modulo" if y.is_zero() => {
+                    let position = for_both!(right, item => item.get_start());
+                    return Err(Exception::create(Error::DivisionByZero(position), current_method!()));
+                },
+                "Divide" => Some(x / y),
+                "Modulo" => {
+                    let remainder = &x - (&x / &y).trunc() * &y;
+                    match self.remainder {
+                        Remainder::Floored if !remainder.is_zero() && remainder.is_negative() != y.is_negative()
+                            => Some(remainder + y),
+                        _ => Some(remainder),
+                    }
+                },
+                "Power" => {
+                    let base = Decimal::from_rational(&x, &self.context);
+                    let exponent = Decimal::from_rational(&y, &self.context);
+                    return match base.pow(&exponent, &self.context) {
+                        None if x.is_zero() || (x.is_negative() && !y.is_integer()) => {
+                            let error = Error::OutOfDomain(operator.get_value(), operator.get_start(), operator.get_next());
+                            Err(Exception::create(error, current_method!()))
+                        },
+                        None => {
+                            let position = for_both!(right, item => item.get_start());
+                            Err(Exception::create(Error::OutOfRange(exponent.to_string(), position), current_method!()))
+                        },
+                        Some(result) => Ok(Value::Decimal(result)),
+                    };
+                },
+                // Bitwise operators are computed on integers.
+                _ => None,
+            };
+            if let Some(result) = result {
+                return Ok(Value::Decimal(Decimal::from_rational(&result, &self.context)));
+            }
+        }
+
+        let rational = matches!((&a, &b), (Value::Rational(_), _) | (_, Value::Rational(_)));
+        if let (true, Some(x), Some(y)) = (rational, Evaluator::get_rational(&a), Evaluator::get_rational(&b)) {
+            match kind.as_str() {
+                "Add" if relative => return Ok(Value::Rational(&x + &x * y)),
+                "Add" => return Ok(Value::Rational(x + y)),
+                "Subtract" if relative => return Ok(Value::Rational(&x - &x * y)),
+                "Subtract" => return Ok(Value::Rational(x - y)),
+                "Multiply" => return Ok(Value::Rational(x * y)),
+                "Divide" if y.is_zero() => {
+                    let position = for_both!(right, item => item.get_start());
+                    return Err(Exception::create(Error::DivisionByZero(position), current_method!()));
+                },
+                "Divide" => return Ok(Value::Rational(x / y)),
+                "Modulo" if y.is_zero() => {
+                    let position = for_both!(right, item => item.get_start());
+                    return Err(Exception::create(Error::DivisionByZero(position), current_method!()));
+                },
+                // `a - b*floor(a/b)`, or `a - b*trunc(a/b)` for a remainder of the sign of the dividend.
+                "Modulo" => {
+                    let quotient = match self.remainder {
+                        Remainder::Floored => (&x / &y).floor(),
+                        Remainder::Truncated => (&x / &y).trunc(),
+                    };
+                    return Ok(Value::Rational(x - y * quotient));
+                },
+                "Power" if y.is_integer() && x.is_zero() && y.is_negative() => {
+                    let position = for_both!(left, item => item.get_start());
+                    return Err(Exception::create(Error::DivisionByZero(position), current_method!()));
+                },
+                // Integral powers stay exact, as long as they are no larger than in integer mode.
+                "Power" if y.is_integer() && y.to_integer().to_i32().is_some_and(|n| {
+                    (x.numer().bits() + x.denom().bits()) * u64::from(n.unsigned_abs()) < MAX_POWER_BITS
+                }) => return Ok(Value::Rational(x.pow(y.to_integer().to_i32().unwrap()))),
+                // Anything else is computed with floats.
+                _ => {},
+            }
+        }
+
+        let exact = self.mode == Mode::Integer && matches!((&a, &b), (Value::Integer(_), Value::Integer(_)));
+        if exact || matches!(kind.as_str(), "BitAnd" | "BitOr" | "BitXor" | "ShiftLeft" | "ShiftRight") {
+            let x = match Evaluator::get_integer(a, left) {
+                Err(error) => return Err(Exception::relay(error, current_method!())),
+                Ok(integer) => integer,
+            };
+            let y = match Evaluator::get_integer(b, right) {
+                Err(error) => return Err(Exception::relay(error, current_method!())),
+                Ok(integer) => integer,
+            };
+            // Bitwise operators also accept integral floats, and give a float back for them.
+            return match self.evaluate_integer(&kind, x, y, right) {
+                Err(error) => Err(Exception::relay(error, current_method!())),
+                Ok(result) if exact => Ok(Value::Integer(result)),
+                Ok(result) => Ok(Evaluator::promote(result)),
+            };
+        }
+
+        // Integral operands are computed exactly, so that `2^1000` isn't rounded to a float.
+        if let (Mode::Float, false, Some(x), Some(y)) = (self.mode, relative, Evaluator::get_exact(&a), Evaluator::get_exact(&b)) {
+            let result = match kind.as_str() {
+                "Add" => Some(x + y),
+                "Subtract" => Some(x - y),
+                "Multiply" => Some(x * y),
+                "Divide" if !y.is_zero() && x.is_multiple_of(&y) => Some(x / y),
+                "Power" if y.to_u64().is_some_and(|n| n < MAX_POWER_BITS && x.bits() * n < MAX_POWER_BITS)
+                    => Some(num_traits::pow(x, y.to_usize().unwrap())),
+                _ => None,
+            };
+            if let Some(result) = result {
+                return Ok(Evaluator::promote(result));
+            }
+        }
+
+        let a = match Evaluator::get_number(a, left) {
+            Err(error) => return Err(Exception::relay(error, current_method!())),
+            Ok(number) => number,
+        };
+        let b = match Evaluator::get_number(b, right) {
+            Err(error) => return Err(Exception::relay(error, current_method!())),
+            Ok(number) => number,
+        };
+        match kind.as_str() {
+            "Add" => Ok(Value::Number(if relative { a + a * b } else { a + b })),
+            "Subtract" => Ok(Value::Number(if relative { a - a * b } else { a - b })),
+            "Multiply" => Ok(Value::Number(a * b)),
+            "Divide" | "Modulo" if b == 0.0 => {
+                let position = for_both!(right, item => item.get_start());
+                Err(Exception::create(Error::DivisionByZero(position), current_method!()))
+            },
+            "Divide" => Ok(Value::Number(a / b)),
+            "Modulo" => {
+                let remainder = a % b;
+                match self.remainder {
+                    Remainder::Floored if remainder != 0.0 && (remainder < 0.0) != (b < 0.0)
+                        => Ok(Value::Number(remainder + b)),
+                    _ => Ok(Value::Number(remainder)),
+                }
+            },
+            "Power" => Ok(Value::Number(a.powf(b))),
+            _ => Err(Exception::create(Error::UnexpectedItem(Right(node.clone().into_owned())), current_method!())),
+        }
+    }
+
+    /// Adds quantities of the same dimension in the unit of the left operand, and multiplies the
+    /// units of products and quotients.
+    fn evaluate_units(
+        &self,
+        node: &Node,
+        x: f64,
+        unit: Option<Unit>,
+        y: f64,
+        other: Option<Unit>,
+        relative: bool,
+    ) -> EvaluatorResult {
+        let value = node.children();
+        let right = value.last().unwrap();
+        match (node.get_type().as_str(), unit, other) {
+            ("Add", Some(unit), None) if relative => Ok(Value::Quantity(x + x * y, unit)),
+            ("Subtract", Some(unit), None) if relative => Ok(Value::Quantity(x - x * y, unit)),
+            ("Add" | "Subtract" | "Modulo", unit, other) => {
+                let (a, b) = (unit.unwrap_or_default(), other.unwrap_or_default());
+                if a.dimension != b.dimension {
+                    return Err(Evaluator::get_incompatible(node, a.get_name(), b.get_name()));
+                }
+                // The right operand is a difference, as `10 K` in `20 °C + 10 K`.
+                let y = a.from_si_difference(b.to_si_difference(y));
+                match node.get_type().as_str() {
+                    "Add" => Ok(Evaluator::from_quantity(x + y, a)),
+                    "Subtract" => Ok(Evaluator::from_quantity(x - y, a)),
+                    _ if y == 0.0 => {
+                        let position = for_both!(right, item => item.get_start());
+                        Err(Exception::create(Error::DivisionByZero(position), current_method!()))
+                    },
+                    _ => Ok(Evaluator::from_quantity(x % y, a)),
+                }
+            },
+            ("Divide", _, _) if y == 0.0 => {
+                let position = for_both!(right, item => item.get_start());
+                Err(Exception::create(Error::DivisionByZero(position), current_method!()))
+            },
+            ("Multiply", Some(unit), None) | ("Multiply", None, Some(unit)) => Ok(Value::Quantity(x * y, unit)),
+            ("Divide", Some(unit), None) => Ok(Value::Quantity(x / y, unit)),
+            ("Divide", None, Some(unit)) => Ok(Evaluator::from_quantity(x / y, unit.power(-1))),
+            ("Multiply", Some(unit), Some(other)) => Ok(Evaluator::from_product(x * y, unit.multiply(&other))),
+            ("Divide", Some(unit), Some(other)) => Ok(Evaluator::from_product(x / y, unit.multiply(&other.power(-1)))),
+            ("Power", Some(unit), None) if y.fract() == 0.0 && y.abs() <= i32::MAX as f64 => {
+                Ok(Evaluator::from_quantity(x.powi(y as i32), unit.power(y as i32)))
+            },
+            _ => {
+                let position = for_both!(right, item => item.get_start());
+                Err(Exception::create(Error::UnexpectedValue(String::from("Quantity"), position), current_method!()))
+            },
+        }
+    }
+
+    /// Points at the whole operation mixing the units, as `3 km + 2 s`.
+    fn get_incompatible(node: &Node, unit: String, other: String) -> Exception {
+        let next = node.get_next();
+        let error = Error::IncompatibleUnits(unit, other, node.get_start(), next);
+        Exception::create(error, current_method!())
+    }
+
+    /// Adds vectors and matrices element-wise, multiplies them as matrices, and solves `A \ b`. A
+    /// vector is a column on the right of a product and a row on its left.
+    fn evaluate_matrix(&self, node: &Node, a: Value, b: Value) -> EvaluatorResult {
+        let value = node.children();
+        let (left, right) = (value.first().unwrap(), value.last().unwrap());
+        let kind = node.get_type();
+        let operator = match value.get(1) {
+            Some(Left(operator)) => operator,
+            _ => return Err(Exception::create(Error::UnexpectedItem(Right(node.clone().into_owned())), current_method!())),
+        };
+        let mismatch = Evaluator::get_mismatch(a.get_shape(), &[left], b.get_shape(), &[right]);
+        let unexpected = |value: &Value, item: &NodeItem| {
+            let position = for_both!(item, item => item.get_start());
+            Exception::create(Error::UnexpectedValue(value.get_type(), position), current_method!())
+        };
+        let singular = || {
+            let error = Error::OutOfDomain(operator.get_value(), operator.get_start(), operator.get_next());
+            Exception::create(error, current_method!())
+        };
+        let division_by_zero = |item: &NodeItem| {
+            let position = for_both!(item, item => item.get_start());
+            Exception::create(Error::DivisionByZero(position), current_method!())
+        };
+        let from = |matrix: Matrix, vector: bool| match vector {
+            true => Value::Vector(matrix.elements),
+            false => Value::Matrix(matrix),
+        };
+        let element: fn(f64, f64) -> f64 = match kind.as_str() {
+            "Add" => |x, y| x + y,
+            "Subtract" => |x, y| x - y,
+            "ElementMultiply" => |x, y| x * y,
+            _ => |x, y| x / y,
+        };
+
+        match (Evaluator::get_matrix(&a), Evaluator::get_matrix(&b)) {
+            (Some((x, u)), Some((y, v))) => match kind.as_str() {
+                "Add" | "Subtract" | "ElementMultiply" | "ElementDivide" if u == v => {
+                    x.zip(&y, element).map(|matrix| from(matrix, u)).ok_or(mismatch)
+                },
+                "Add" | "Subtract" | "ElementMultiply" | "ElementDivide" => Err(mismatch),
+                "Multiply" if u && v => Err(mismatch),
+                "Multiply" => {
+                    let x = if u { x.transpose() } else { x };
+                    x.multiply(&y).map(|matrix| from(matrix, u || v)).ok_or(mismatch)
+                },
+                "Solve" if !u && x.is_square() && x.rows == y.rows => {
+                    x.solve(&y).map(|matrix| from(matrix, v)).ok_or_else(singular)
+                },
+                "Solve" => Err(mismatch),
+                _ => Err(unexpected(&b, right)),
+            },
+            (Some((x, u)), None) => {
+                let y = match Evaluator::get_number(b.clone(), right) {
+                    Err(error) => return Err(Exception::relay(error, current_method!())),
+                    Ok(number) => number,
+                };
+                match kind.as_str() {
+                    "Multiply" | "ElementMultiply" => Ok(from(x.map(|e| e * y), u)),
+                    "Divide" | "ElementDivide" if y == 0.0 => Err(division_by_zero(right)),
+                    "Divide" | "ElementDivide" => Ok(from(x.map(|e| e / y), u)),
+                    "Power" if !u && x.is_square() && y.fract() == 0.0 && y.abs() <= i32::MAX as f64 => {
+                        x.power(y as i32).map(Value::Matrix).ok_or_else(singular)
+                    },
+                    "Power" if !u && x.is_square() => Err(unexpected(&b, right)),
+                    "Add" | "Subtract" | "Solve" => Err(mismatch),
+                    _ => Err(unexpected(&a, left)),
+                }
+            },
+            (None, Some((y, v))) => {
+                let x = match Evaluator::get_number(a.clone(), left) {
+                    Err(error) => return Err(Exception::relay(error, current_method!())),
+                    Ok(number) => number,
+                };
+                match kind.as_str() {
+                    "Multiply" | "ElementMultiply" => Ok(from(y.map(|e| x * e), v)),
+                    "ElementDivide" => Ok(from(y.map(|e| x / e), v)),
+                    "Solve" if x == 0.0 => Err(division_by_zero(left)),
+                    "Solve" => Ok(from(y.map(|e| e / x), v)),
+                    "Add" | "Subtract" => Err(mismatch),
+                    _ => Err(unexpected(&b, right)),
+                }
+            },
+            (None, None) => {
+                let x = match Evaluator::get_number(a, left) {
+                    Err(error) => return Err(Exception::relay(error, current_method!())),
+                    Ok(number) => number,
+                };
+                let y = match Evaluator::get_number(b, right) {
+                    Err(error) => return Err(Exception::relay(error, current_method!())),
+                    Ok(number) => number,
+                };
+                match kind.as_str() {
+                    "ElementMultiply" => Ok(Value::Number(x * y)),
+                    "ElementDivide" if y == 0.0 => Err(division_by_zero(right)),
+                    "ElementDivide" => Ok(Value::Number(x / y)),
+                    "Solve" if x == 0.0 => Err(division_by_zero(left)),
+                    "Solve" => Ok(Value::Number(y / x)),
+                    _ => Err(Exception::create(Error::UnexpectedItem(Right(node.clone().into_owned())), current_method!())),
+                }
+            },
+        }
+    }
+
+    /// Reads a vector as a matrix of one column, telling whether it was a vector.
+    fn get_matrix(value: &Value) -> Option<(Matrix, bool)> {
+        match value {
+            Value::Matrix(matrix) => Some((matrix.clone(), false)),
+            Value::Vector(vector) => Some((Matrix::from_column(vector), true)),
+            _ => None,
+        }
+    }
+
+    /// Points at both operands whose shapes don't agree, as `[1, 2] + [1, 2, 3]`.
+    fn get_mismatch(shape: String, items: &[&NodeItem], other: String, other_items: &[&NodeItem]) -> Exception {
+        let (start, next) = Evaluator::get_span(items);
+        let (other_start, other_next) = Evaluator::get_span(other_items);
+        let error = Error::ShapeMismatch(shape, start, next, other, other_start, other_next);
+        Exception::create(error, current_method!())
+    }
+
+    /// Gives the start of the first item and the end of the last one.
+    fn get_span(items: &[&NodeItem]) -> (Position, Position) {
+        let start = for_both!(items[0], item => item.get_start());
+        let next = for_both!(items[items.len() - 1], item => item.get_next());
+        (start, next)
+    }
+
+    /// Shifts dates and times by durations, and measures the durations between them. Durations also
+    /// combine with time quantities such as `90 days`.
+    fn evaluate_temporal(&self, node: &Node, a: Value, b: Value) -> EvaluatorResult {
+        let value = node.children();
+        let (left, right) = (value.first().unwrap(), value.last().unwrap());
+        let kind = node.get_type();
+        let operator = match value.get(1) {
+            Some(Left(operator)) => operator,
+            _ => return Err(Exception::create(Error::UnexpectedItem(Right(node.clone().into_owned())), current_method!())),
+        };
+        let shifted = |value: Option<Value>| match value {
+            None => Err(Exception::create(Error::OutOfRange(kind.clone(), operator.get_start()), current_method!())),
+            Some(value) => Ok(value),
+        };
+        match (kind.as_str(), a, b) {
+            ("Subtract", Value::Time(x), Value::Time(y)) => Ok(Value::Duration(x - y)),
+            ("Subtract", a, b) if get_date_time(&a).is_some() && get_date_time(&b).is_some() => {
+                Ok(Value::Duration(get_date_time(&a).unwrap() - get_date_time(&b).unwrap()))
+            },
+            ("Add", a @ (Value::Date(_) | Value::DateTime(_) | Value::Time(_)), b) => match Evaluator::get_duration(b, right) {
+                Err(error) => Err(Exception::relay(error, current_method!())),
+                Ok(duration) => shifted(Evaluator::shift(a, duration)),
+            },
+            ("Add", a, b @ (Value::Date(_) | Value::DateTime(_) | Value::Time(_))) => match Evaluator::get_duration(a, left) {
+                Err(error) => Err(Exception::relay(error, current_method!())),
+                Ok(duration) => shifted(Evaluator::shift(b, duration)),
+            },
+            ("Subtract", a @ (Value::Date(_) | Value::DateTime(_) | Value::Time(_)), b) => match Evaluator::get_duration(b, right) {
+                Err(error) => Err(Exception::relay(error, current_method!())),
+                Ok(duration) => shifted(Evaluator::shift(a, -duration)),
+            },
+            (kind @ ("Add" | "Subtract"), a, b) => {
+                let x = match Evaluator::get_duration(a, left) {
+                    Err(error) => return Err(Exception::relay(error, current_method!())),
+                    Ok(duration) => duration,
+                };
+                match Evaluator::get_duration(b, right) {
+                    Err(error) => Err(Exception::relay(error, current_method!())),
+                    Ok(y) if kind == "Add" => shifted(x.checked_add(&y).map(Value::Duration)),
+                    Ok(y) => shifted(x.checked_sub(&y).map(Value::Duration)),
+                }
+            },
+            // With a quantity, a duration is a number of seconds, so that `60 mph * 2 h` works alike
+            // for both.
+            ("Multiply" | "Divide", a, b) if matches!((&a, &b), (Value::Quantity(_, _), _) | (_, Value::Quantity(_, _)) | (_, Value::Duration(_))) => {
+                let (x, unit) = match Evaluator::get_quantity(a, left) {
+                    Err(error) => return Err(Exception::relay(error, current_method!())),
+                    Ok(quantity) => quantity,
+                };
+                let (y, other) = match Evaluator::get_quantity(b, right) {
+                    Err(error) => return Err(Exception::relay(error, current_method!())),
+                    Ok(quantity) => quantity,
+                };
+                match self.evaluate_units(node, x, unit, y, other, false) {
+                    Err(error) => Err(Exception::relay(error, current_method!())),
+                    Ok(value) => Ok(value),
+                }
+            },
+            ("Multiply", Value::Duration(x), b) => match Evaluator::get_number(b, right) {
+                Err(error) => Err(Exception::relay(error, current_method!())),
+                Ok(factor) => shifted(from_seconds(get_seconds(&x) * factor).map(Value::Duration)),
+            },
+            ("Multiply", a, Value::Duration(y)) => match Evaluator::get_number(a, left) {
+                Err(error) => Err(Exception::relay(error, current_method!())),
+                Ok(factor) => shifted(from_seconds(factor * get_seconds(&y)).map(Value::Duration)),
+            },
+            ("Divide", Value::Duration(x), b) => match Evaluator::get_number(b, right) {
+                Err(error) => Err(Exception::relay(error, current_method!())),
+                Ok(0.0) => {
+                    let position = for_both!(right, item => item.get_start());
+                    Err(Exception::create(Error::DivisionByZero(position), current_method!()))
+                },
+                Ok(divisor) => shifted(from_seconds(get_seconds(&x) / divisor).map(Value::Duration)),
+            },
+            (_, a, b) => {
+                let (value, item) = match a {
+                    Value::Date(_) | Value::DateTime(_) | Value::Time(_) | Value::Duration(_) => (a, left),
+                    _ => (b, right),
+                };
+                let position = for_both!(item, item => item.get_start());
+                Err(Exception::create(Error::UnexpectedValue(value.get_type(), position), current_method!()))
+            },
+        }
+    }
+
+    /// Moves a date, a date and time or a time of day by a duration. A date stays a date when the
+    /// duration is a whole number of days, and a time of day wraps around midnight.
+    fn shift(value: Value, duration: TimeDelta) -> Option<Value> {
+        match value {
+            Value::Date(date) if duration.num_seconds() % 86_400 == 0 && duration.subsec_nanos() == 0 => {
+                date.checked_add_signed(duration).map(Value::Date)
+            },
+            Value::Time(time) => Some(Value::Time(time.overflowing_add_signed(duration).0)),
+            value => get_date_time(&value)?.checked_add_signed(duration).map(Value::DateTime),
+        }
+    }
+
+    /// Reads a duration, or a time quantity such as `90 days`.
+    fn get_duration(value: Value, item: &NodeItem) -> Result<TimeDelta, Exception> {
+        let position = for_both!(item, item => item.get_start());
+        match value {
+            Value::Duration(duration) => Ok(duration),
+            Value::Quantity(magnitude, unit) if unit.dimension == Unit::parse("s").unwrap().dimension => {
+                match from_seconds(unit.to_si(magnitude)) {
+                    None => Err(Exception::create(Error::OutOfRange(Value::Quantity(magnitude, unit).to_string(), position), current_method!())),
+                    Some(duration) => Ok(duration),
+                }
+            },
+            value => Err(Exception::create(Error::UnexpectedValue(value.get_type(), position), current_method!())),
+        }
+    }
+
+    /// Adds amounts in the currency of the left operand, and scales them by plain numbers.
+    fn evaluate_money(&self, node: &Node, a: Value, b: Value, relative: bool) -> EvaluatorResult {
+        let value = node.children();
+        let (left, right) = (value.first().unwrap(), value.last().unwrap());
+        let kind = node.get_type();
+        match (a, b) {
+            (Value::Money(x), Value::Money(y)) => {
+                let y = match self.exchange(y, x.currency, node) {
+                    Err(error) => return Err(Exception::relay(error, current_method!())),
+                    Ok(money) => money,
+                };
+                let timestamp = y.timestamp.or(x.timestamp);
+                match kind.as_str() {
+                    "Add" => Ok(self.get_money(x.amount + y.amount, x.currency, timestamp)),
+                    "Subtract" => Ok(self.get_money(x.amount - y.amount, x.currency, timestamp)),
+                    "Divide" if y.amount.is_zero() => {
+                        let position = for_both!(right, item => item.get_start());
+                        Err(Exception::create(Error::DivisionByZero(position), current_method!()))
+                    },
+                    "Divide" => Ok(Value::Number((x.amount / y.amount).to_f64().unwrap_or(f64::NAN))),
+                    _ => {
+                        let position = for_both!(right, item => item.get_start());
+                        Err(Exception::create(Error::UnexpectedValue(String::from("Money"), position), current_method!()))
+                    },
+                }
+            },
+            (Value::Money(x), b) => {
+                let y = match Evaluator::get_decimal_rational(b, right) {
+                    Err(error) => return Err(Exception::relay(error, current_method!())),
+                    Ok(rational) => rational,
+                };
+                match kind.as_str() {
+                    "Add" if relative => Ok(self.get_money(&x.amount + &x.amount * y, x.currency, x.timestamp)),
+                    "Subtract" if relative => Ok(self.get_money(&x.amount - &x.amount * y, x.currency, x.timestamp)),
+                    "Add" | "Subtract" => {
+                        Err(Evaluator::get_incompatible(node, String::from(x.currency.code), String::from("1")))
+                    },
+                    "Multiply" => Ok(self.get_money(x.amount * y, x.currency, x.timestamp)),
+                    "Divide" if y.is_zero() => {
+                        let position = for_both!(right, item => item.get_start());
+                        Err(Exception::create(Error::DivisionByZero(position), current_method!()))
+                    },
+                    "Divide" => Ok(self.get_money(x.amount / y, x.currency, x.timestamp)),
+                    _ => {
+                        let position = for_both!(left, item => item.get_start());
+                        Err(Exception::create(Error::UnexpectedValue(String::from("Money"), position), current_method!()))
+                    },
+                }
+            },
+            (a, Value::Money(y)) => {
+                let x = match Evaluator::get_decimal_rational(a, left) {
+                    Err(error) => return Err(Exception::relay(error, current_method!())),
+                    Ok(rational) => rational,
+                };
+                match kind.as_str() {
+                    "Multiply" => Ok(self.get_money(x * y.amount, y.currency, y.timestamp)),
+                    "Add" | "Subtract" => {
+                        Err(Evaluator::get_incompatible(node, String::from("1"), String::from(y.currency.code)))
+                    },
+                    _ => {
+                        let position = for_both!(right, item => item.get_start());
+                        Err(Exception::create(Error::UnexpectedValue(String::from("Money"), position), current_method!()))
+                    },
+                }
+            },
+            _ => Err(Exception::create(Error::UnexpectedItem(Right(node.clone().into_owned())), current_method!())),
+        }
+    }
+
+    /// Rounds an amount to the minor unit of its currency.
+    fn get_money(&self, amount: BigRational, currency: &'static Currency, timestamp: Option<String>) -> Value {
+        let amount = self.context.rounding.round(&amount, currency.minor);
+        Value::Money(Money { amount, currency, timestamp })
+    }
+
+    /// Converts an amount with the exchange rates, keeping the timestamp of the rates used.
+    fn exchange(&self, money: Money, currency: &'static Currency, node: &Node) -> Result<Money, Exception> {
+        match self.rates.convert(&money.amount, money.currency, currency) {
+            None => {
+                let next = node.get_next();
+                let error = Error::UnknownRate(String::from(money.currency.code), String::from(currency.code), node.get_start(), next);
+                Err(Exception::create(error, current_method!()))
+            },
+            Some((amount, timestamp)) => {
+                let amount = self.context.rounding.round(&amount, currency.minor);
+                Ok(Money { amount, currency, timestamp: timestamp.or(money.timestamp) })
+            },
+        }
+    }
+
+    /// Gives a plain number once all the units cancel out.
+    fn from_quantity(magnitude: f64, unit: Unit) -> Value {
+        match unit.terms.is_empty() {
+            true => Value::Number(unit.to_si(magnitude)),
+            false => Value::Quantity(magnitude, unit),
+        }
+    }
+
+    /// Simplifies the units of a product, so that `kg·m/s^2` is written `N` and `km/m` is a number.
+    fn from_product(magnitude: f64, unit: Unit) -> Value {
+        if unit.dimension.is_none() {
+            return Value::Number(unit.to_si(magnitude));
+        }
+        let simple = unit.simplify();
+        Value::Quantity(magnitude * unit.scale / simple.scale, simple)
+    }
+
+    fn evaluate_fixed(
+        &self,
+        kind: &str,
+        a: BigInt,
+        b: BigInt,
+        integer_type: IntegerType,
+        operator: &Token,
+        right: &NodeItem,
+    ) -> Result<BigInt, Exception> {
+        let bits = BigInt::from(integer_type.bits);
+        // Powers and shifts that can only overflow aren't computed exactly, the wrapped result and
+        // the sign of the exact one being enough for every overflow policy.
+        let overflowing = match kind {
+            "Power" if bits < b && BigInt::one() < a.abs() => {
+                let wrapped = integer_type.wrap(&a.modpow(&b, &(BigInt::one() << integer_type.bits)));
+                Some((wrapped, a.is_negative() && b.is_odd()))
+            },
+            "ShiftLeft" if bits <= b && !a.is_zero() => Some((BigInt::zero(), a.is_negative())),
+            _ => None,
+        };
+        if let Some((wrapped, negative)) = overflowing {
+            return match self.overflow {
+                Overflow::Wrapping => Ok(wrapped),
+                Overflow::Saturating => Ok(if negative { integer_type.min() } else { integer_type.max() }),
+                Overflow::Error => {
+                    let error = Error::Overflow(integer_type.to_string(), operator.get_start());
+                    Err(Exception::create(error, current_method!()))
+                },
+            };
+        }
+
+        match self.evaluate_integer(kind, a, b, right) {
+            Err(error) => Err(Exception::relay(error, current_method!())),
+            // Bitwise operators work on the bits themselves and never overflow.
+            Ok(result) if matches!(kind, "BitAnd" | "BitOr" | "BitXor") => Ok(integer_type.wrap(&result)),
+            Ok(result) => match self.fit(result, integer_type, operator) {
+                Err(error) => Err(Exception::relay(error, current_method!())),
+                Ok(result) => Ok(result),
+            },
+        }
+    }
+
+    fn fit(&self, value: BigInt, integer_type: IntegerType, operator: &Token) -> Result<BigInt, Exception> {
+        match self.try_fit(value, integer_type) {
+            None => {
+                let error = Error::Overflow(integer_type.to_string(), operator.get_start());
+                Err(Exception::create(error, current_method!()))
+            },
+            Some(value) => Ok(value),
+        }
+    }
+
+    /// Brings a value into the range of its type as the overflow policy says, giving `None` when
+    /// the policy is to report it.
+    fn try_fit(&self, value: BigInt, integer_type: IntegerType) -> Option<BigInt> {
+        if integer_type.contains(&value) {
+            return Some(value);
+        }
+        match self.overflow {
+            Overflow::Wrapping => Some(integer_type.wrap(&value)),
+            Overflow::Saturating => Some(integer_type.saturate(&value)),
+            Overflow::Error => None,
+        }
+    }
+
+    fn evaluate_integer(&self, kind: &str, a: BigInt, b: BigInt, right: &NodeItem) -> Result<BigInt, Exception> {
+        let position = for_both!(right, item => item.get_start());
+        match kind {
+            "Add" => Ok(a + b),
+            "Subtract" => Ok(a - b),
+            "Multiply" => Ok(a * b),
+            "Divide" | "Modulo" if b.is_zero() => {
+                Err(Exception::create(Error::DivisionByZero(position), current_method!()))
+            },
+            "Divide" => Ok(a / b),
+            "Modulo" => match self.remainder {
+                Remainder::Truncated => Ok(a % b),
+                Remainder::Floored => Ok(a.mod_floor(&b)),
+            },
+            "Power" => match b.to_u32() {
+                Some(exponent) if a.magnitude().is_one() || a.is_zero() => Ok(a.pow(exponent)),
+                Some(exponent) if a.bits() * u64::from(exponent) < MAX_POWER_BITS => Ok(a.pow(exponent)),
+                None if b.is_negative() => Err(Exception::create(Error::NegativeExponent(position), current_method!())),
+                _ => Err(Exception::create(Error::OutOfRange(b.to_string(), position), current_method!())),
+            },
+            "BitAnd" => Ok(a & b),
+            "BitOr" => Ok(a | b),
+            "BitXor" => Ok(a ^ b),
+            "ShiftLeft" | "ShiftRight" => match b.to_usize() {
+                None => Err(Exception::create(Error::OutOfRange(b.to_string(), position), current_method!())),
+                Some(shift) if kind == "ShiftLeft" && !a.is_zero() && a.bits() + shift as u64 >= MAX_POWER_BITS => {
+                    Err(Exception::create(Error::OutOfRange(b.to_string(), position), current_method!()))
+                },
+                Some(shift) if kind == "ShiftLeft" => Ok(a << shift),
+                Some(shift) => Ok(a >> shift),
+            },
+            _ => Err(Exception::create(Error::UnexpectedValue(String::from("Integer"), position), current_method!())),
+        }
+    }
+
+    /// Compares the values of the operands, out of line like `evaluate_operation`.
+    #[inline(never)]
+    fn compare(&self, node: &Node, a: Value, b: Value) -> EvaluatorResult {
+        let value = node.children();
+        let (left, right) = (&value[0], &value[2]);
+
+        // Neither have vectors and matrices, which are equal when all their elements are.
+        if let (Value::Vector(_) | Value::Matrix(_), _) | (_, Value::Vector(_) | Value::Matrix(_)) = (&a, &b) {
+            let array = if matches!(a, Value::Vector(_) | Value::Matrix(_)) { left } else { right };
+            let position = for_both!(array, item => item.get_start());
+            return match node.get_type().as_str() {
+                "Equal" => Ok(Value::Boolean(a == b)),
+                "NotEqual" => Ok(Value::Boolean(a != b)),
+                _ => Err(Exception::create(Error::UnexpectedValue(String::from("Matrix"), position), current_method!())),
+            };
+        }
+
+        // Complex numbers have no order, but can be compared for equality.
+        if let (Value::Complex(_), _) | (_, Value::Complex(_)) = (&a, &b) {
+            let complex = if matches!(a, Value::Complex(_)) { left } else { right };
+            let position = for_both!(complex, item => item.get_start());
+            let x = match Evaluator::get_complex(a, left) {
+                Err(error) => return Err(Exception::relay(error, current_method!())),
+                Ok(complex) => complex,
+            };
+            return match (node.get_type().as_str(), Evaluator::get_complex(b, right)) {
+                (_, Err(error)) => Err(Exception::relay(error, current_method!())),
+                ("Equal", Ok(y)) => Ok(Value::Boolean(x == y)),
+                ("NotEqual", Ok(y)) => Ok(Value::Boolean(x != y)),
+                _ => Err(Exception::create(Error::UnexpectedValue(String::from("Complex"), position), current_method!())),
+            };
+        }
+
+        let ordering = match (a, b) {
+            (a @ Value::Money(_), b) | (a, b @ Value::Money(_)) => match (a, b) {
+                (Value::Money(x), Value::Money(y)) => match self.exchange(y, x.currency, node) {
+                    Err(error) => return Err(Exception::relay(error, current_method!())),
+                    Ok(y) => x.amount.partial_cmp(&y.amount),
+                },
+                (a, b) => {
+                    let name = |value: &Value| match value {
+                        Value::Money(money) => String::from(money.currency.code),
+                        _ => String::from("1"),
+                    };
+                    return Err(Evaluator::get_incompatible(node, name(&a), name(&b)));
+                },
+            },
+            (Value::Time(x), Value::Time(y)) => x.partial_cmp(&y),
+            (a, b) if get_date_time(&a).is_some() && get_date_time(&b).is_some() => {
+                get_date_time(&a).partial_cmp(&get_date_time(&b))
+            },
+            (a @ Value::Duration(_), b) | (a, b @ Value::Duration(_)) => {
+                let x = match Evaluator::get_duration(a, left) {
+                    Err(error) => return Err(Exception::relay(error, current_method!())),
+                    Ok(duration) => duration,
+                };
+                match Evaluator::get_duration(b, right) {
+                    Err(error) => return Err(Exception::relay(error, current_method!())),
+                    Ok(y) => x.partial_cmp(&y),
+                }
+            },
+            // Quantities are compared in SI units, `1 km == 1000 m` being true.
+            (a @ Value::Quantity(_, _), b) | (a, b @ Value::Quantity(_, _)) => {
+                let (x, unit) = match Evaluator::get_quantity(a, left) {
+                    Err(error) => return Err(Exception::relay(error, current_method!())),
+                    Ok((x, unit)) => (x, unit.unwrap_or_default()),
+                };
+                let (y, other) = match Evaluator::get_quantity(b, right) {
+                    Err(error) => return Err(Exception::relay(error, current_method!())),
+                    Ok((y, other)) => (y, other.unwrap_or_default()),
+                };
+                if unit.dimension != other.dimension {
+                    return Err(Evaluator::get_incompatible(node, unit.get_name(), other.get_name()));
+                }
+                unit.to_si(x).partial_cmp(&other.to_si(y))
+            },
+            (Value::Integer(a) | Value::Fixed(a, _), Value::Integer(b) | Value::Fixed(b, _)) => a.partial_cmp(&b),
+            (a, b) if Evaluator::get_rational(&a).is_some() && Evaluator::get_rational(&b).is_some() => {
+                Evaluator::get_rational(&a).partial_cmp(&Evaluator::get_rational(&b))
+            },
+            (Value::Boolean(a), Value::Boolean(b)) if matches!(node.get_type().as_str(), "Equal" | "NotEqual") => a.partial_cmp(&b),
+            (a, b) => {
+                let a = match Evaluator::get_number(a, left) {
+                    Err(error) => return Err(Exception::relay(error, current_method!())),
+                    Ok(number) => number,
+                };
+                match Evaluator::get_number(b, right) {
+                    Err(error) => return Err(Exception::relay(error, current_method!())),
+                    Ok(b) => a.partial_cmp(&b),
+                }
+            },
+        };
+
+        match node.get_type().as_str() {
+            "Equal" => Ok(Value::Boolean(ordering.is_some_and(|ordering| ordering.is_eq()))),
+            "NotEqual" => Ok(Value::Boolean(!ordering.is_some_and(|ordering| ordering.is_eq()))),
+            "Less" => Ok(Value::Boolean(ordering.is_some_and(|ordering| ordering.is_lt()))),
+            "LessEqual" => Ok(Value::Boolean(ordering.is_some_and(|ordering| ordering.is_le()))),
+            "Greater" => Ok(Value::Boolean(ordering.is_some_and(|ordering| ordering.is_gt()))),
+            "GreaterEqual" => Ok(Value::Boolean(ordering.is_some_and(|ordering| ordering.is_ge()))),
+            _ => Err(Exception::create(Error::UnexpectedItem(Right(node.clone().into_owned())), current_method!())),
+        }
+    }
+
+    /// `&&` and `||` only evaluate their right operand when the left one doesn't decide the result.
+    fn evaluate_not(&mut self, node: &Node) -> EvaluatorResult {
+        match node.children().last() {
+            None => Err(Exception::create(Error::UnexpectedItem(Right(node.clone().into_owned())), current_method!())),
+            Some(item) => match self.evaluate_condition(item) {
+                Err(error) => Err(Exception::relay(error, current_method!())),
+                Ok(value) => Ok(Value::Boolean(!value)),
+            },
+        }
+    }
+
+    fn evaluate_conditional(&mut self, node: &Node) -> EvaluatorResult {
+        let value = node.children();
+        let (condition, consequent, alternative) = match (value.first(), value.get(2), value.get(4)) {
+            (Some(condition), Some(consequent), Some(alternative)) => (condition, consequent, alternative),
+            _ => return Err(Exception::create(Error::UnexpectedItem(Right(node.clone().into_owned())), current_method!())),
+        };
+
+        let branch = match self.evaluate_condition(condition) {
+            Err(error) => return Err(Exception::relay(error, current_method!())),
+            Ok(true) => consequent,
+            Ok(false) => alternative,
+        };
+        match self.evaluate_item(branch) {
+            Err(error) => Err(Exception::relay(error, current_method!())),
+            Ok(value) => Ok(value),
+        }
+    }
+
+    fn evaluate_condition(&mut self, item: &NodeItem) -> Result<bool, Exception> {
+        match self.evaluate_item(item) {
+            Err(error) => Err(Exception::relay(error, current_method!())),
+            Ok(value) => Evaluator::get_condition(value, item),
+        }
+    }
+
+    /// Reads a condition as a boolean, numbers being true unless they are zero as in `x && y`.
+    fn get_condition(value: Value, item: &NodeItem) -> Result<bool, Exception> {
+        match value {
+            Value::Boolean(value) => Ok(value),
+            Value::Number(value) => Ok(value != 0.0),
+            Value::Complex(value) => Ok(!value.is_zero()),
+            Value::Decimal(value) => Ok(!value.is_zero()),
+            Value::Integer(value) | Value::Fixed(value, _) => Ok(!value.is_zero()),
+            Value::Rational(value) => Ok(!value.is_zero()),
+            value => {
+                let position = for_both!(item, item => item.get_start());
+                Err(Exception::create(Error::UnexpectedValue(value.get_type(), position), current_method!()))
+            }
+        }
+    }
+
+    fn evaluate_unary(&mut self, node: &Node) -> EvaluatorResult {
+        let value = node.children();
+        let item = match value.iter().find(|item| !matches!(item, Left(token) if token.is_operator(None))) {
+            None => return Err(Exception::create(Error::UnexpectedItem(Right(node.clone().into_owned())), current_method!())),
+            Some(item) => item,
+        };
+        // A negated literal is read as a whole so that `-128i8` stays in range.
+        if let (Left(token), "Negate") = (Evaluator::get_inner(item), node.get_type().as_str()) {
+            if token.is_number(None) {
+                return self.evaluate_number(token, true);
+            }
+        }
+        let operand = match self.evaluate_item(item) {
+            Err(error) => return Err(Exception::relay(error, current_method!())),
+            Ok(operand) => operand,
+        };
+
+        match self.apply_unary(node, item, operand) {
+            Err(error) => Err(Exception::relay(error, current_method!())),
+            Ok(value) => Ok(value),
+        }
+    }
+
+    /// Applies a prefix or postfix operator to the value of its operand, out of line like
+    /// `evaluate_operation`.
+    #[inline(never)]
+    fn apply_unary(&self, node: &Node, item: &NodeItem, operand: Value) -> EvaluatorResult {
+        let operator = node.children().iter().find_map(|item| item.as_ref().left().filter(|token| token.is_operator(None))).unwrap();
+        match (node.get_type().as_str(), operand) {
+            ("Factorial", operand) => match Evaluator::get_integer(operand.clone(), item) {
+                Err(error) => Err(Exception::relay(error, current_method!())),
+                Ok(n) if n.is_negative() || BigInt::from(MAX_FACTORIAL) < n => {
+                    let error = Error::OutOfDomain(operator.get_value(), operator.get_start(), operator.get_next());
+                    Err(Exception::create(error, current_method!()))
+                },
+                Ok(n) => {
+                    let result: BigInt = (1..=n.to_u64().unwrap()).map(BigInt::from).product();
+                    match operand {
+                        Value::Fixed(_, integer_type) => match self.fit(result, integer_type, operator) {
+                            Err(error) => Err(Exception::relay(error, current_method!())),
+                            Ok(result) => Ok(Value::Fixed(result, integer_type)),
+                        },
+                        Value::Integer(_) => Ok(Value::Integer(result)),
+                        Value::Rational(_) => Ok(Value::Rational(BigRational::from_integer(result))),
+                        Value::Decimal(_) => {
+                            Ok(Value::Decimal(Decimal::from_rational(&BigRational::from_integer(result), &self.context)))
+                        },
+                        _ => Ok(Evaluator::promote(result)),
+                    }
+                },
+            },
+            ("Negate", Value::Fixed(a, integer_type)) => {
+                match self.fit(-a, integer_type, operator) {
+                    Err(error) => Err(Exception::relay(error, current_method!())),
+                    Ok(result) => Ok(Value::Fixed(result, integer_type)),
+                }
+            },
+            ("Identity", Value::Fixed(a, integer_type)) => Ok(Value::Fixed(a, integer_type)),
+            ("BitNot", Value::Fixed(a, integer_type)) => Ok(Value::Fixed(integer_type.wrap(&!a), integer_type)),
+            ("Negate", Value::Integer(a)) => Ok(Value::Integer(-a)),
+            ("Identity", Value::Integer(a)) => Ok(Value::Integer(a)),
+            ("BitNot", Value::Integer(a)) => Ok(Value::Integer(!a)),
+            ("Negate", Value::Complex(a)) => Ok(Value::Complex(-a)),
+            ("Identity", Value::Complex(a)) => Ok(Value::Complex(a)),
+            ("Percent", Value::Complex(a)) => Ok(Value::Complex(a / 100.0)),
+            ("Negate", Value::Decimal(a)) => Ok(Value::Decimal(Decimal { digits: -a.digits, exponent: a.exponent })),
+            ("Identity", Value::Decimal(a)) => Ok(Value::Decimal(a)),
+            ("Percent", Value::Decimal(a)) => {
+                let percent = a.to_rational() / BigInt::from(100);
+                Ok(Value::Decimal(Decimal::from_rational(&percent, &self.context)))
+            },
+            ("Negate", Value::Rational(a)) => Ok(Value::Rational(-a)),
+            ("Identity", Value::Rational(a)) => Ok(Value::Rational(a)),
+            ("Percent", Value::Rational(a)) => Ok(Value::Rational(a / BigInt::from(100))),
+            ("Negate", Value::Quantity(a, unit)) => Ok(Value::Quantity(-a, unit)),
+            ("Identity", Value::Quantity(a, unit)) => Ok(Value::Quantity(a, unit)),
+            ("Percent", Value::Quantity(a, unit)) => Ok(Value::Quantity(a / 100.0, unit)),
+            ("Negate", Value::Duration(a)) => Ok(Value::Duration(-a)),
+            ("Identity", Value::Duration(a)) => Ok(Value::Duration(a)),
+            ("Negate", Value::Vector(a)) => Ok(Value::Vector(a.iter().map(|x| -x).collect())),
+            ("Identity", Value::Vector(a)) => Ok(Value::Vector(a)),
+            ("Negate", Value::Matrix(a)) => Ok(Value::Matrix(a.map(|x| -x))),
+            ("Identity", Value::Matrix(a)) => Ok(Value::Matrix(a)),
+            ("Negate", Value::Money(a)) => Ok(Value::Money(Money { amount: -a.amount, ..a })),
+            ("Identity", Value::Money(a)) => Ok(Value::Money(a)),
+            ("Percent", Value::Money(a)) => Ok(self.get_money(a.amount / BigInt::from(100), a.currency, a.timestamp)),
+            ("BitNot", operand) => match Evaluator::get_integer(operand, item) {
+                Err(error) => Err(Exception::relay(error, current_method!())),
+                Ok(a) => Ok(Value::Number((!a).to_f64().unwrap_or(f64::NAN))),
+            },
+            (kind, operand) => {
+                let a = match Evaluator::get_number(operand, item) {
+                    Err(error) => return Err(Exception::relay(error, current_method!())),
+                    Ok(number) => number,
+                };
+                match kind {
+                    "Negate" => Ok(Value::Number(-a)),
+                    "Identity" => Ok(Value::Number(a)),
+                    "Percent" => Ok(Value::Number(a / 100.0)),
+                    _ => Err(Exception::create(Error::UnexpectedItem(Right(node.clone().into_owned())), current_method!())),
+                }
+            }
+        }
+    }
+
+    fn evaluate_call(&mut self, node: &Node) -> EvaluatorResult {
+        let value = node.children();
+        let name = match value.first() {
+            Some(Left(token)) => token.clone(),
+            _ => return Err(Exception::create(Error::UnexpectedItem(Right(node.clone().into_owned())), current_method!())),
+        };
+
+        let items: Vec<&NodeItem> = value.iter()
+            .skip(1)
+            .filter(|item| !matches!(item, Left(token) if token.is_punctuation(None)))
+            .collect();
+
+        let mut arguments: Vec<Value> = vec![];
+        for item in &items {
+            match self.evaluate_item(item) {
+                Err(error) => return Err(Exception::relay(error, current_method!())),
+                Ok(argument) => arguments.push(argument),
+            }
+        }
+
+        let start = name.get_start();
+        let next = node.get_next();
+
+        if let Some(definition) = self.environment.get_function(&name.get_value()) {
+            if definition.parameters.len() != arguments.len() {
+                let expected = definition.parameters.len().to_string();
+                let error = Error::InvalidArity(name.get_value(), expected, arguments.len(), start, next);
+                return Err(Exception::create(error, current_method!()));
+            }
+            // A quarter of the frames is left to the body of the last call.
+            if self.depth >= self.max_depth || self.frames >= MAX_FRAMES - MAX_FRAMES / 4 {
+                let error = Error::RecursionLimit(name.get_value(), self.depth, start, next);
+                return Err(Exception::create(error, current_method!()));
+            }
+
+            let locals = self.environment.enter(&definition, arguments);
+            self.depth += 1;
+            let result = self.evaluate_item(&definition.body);
+            self.depth -= 1;
+            self.environment.leave(locals);
+
+            return match result {
+                Err(error) => Err(Exception::relay(error, current_method!())),
+                Ok(value) => Ok(value),
+            };
+        }
+
+        match self.call_builtin(&name, &items, arguments, start, next) {
+            Err(error) => Err(Exception::relay(error, current_method!())),
+            Ok(value) => Ok(value),
+        }
+    }
+
+    /// Calls a function of the library on the values of its arguments, out of line like
+    /// `evaluate_operation`.
+    #[inline(never)]
+    fn call_builtin(&self, name: &Token, items: &[&NodeItem], mut arguments: Vec<Value>, start: Position, next: Position) -> EvaluatorResult {
+        if let Some(function) = get_matrix_function(&name.get_value()) {
+            if arguments.len() != function.arity {
+                let error = Error::InvalidArity(name.get_value(), function.arity.to_string(), arguments.len(), start, next);
+                return Err(Exception::create(error, current_method!()));
+            }
+            // Scalars are read as floats, so that `identity(3)` works in every mode.
+            let arguments: Vec<Value> = arguments.into_iter()
+                .zip(items)
+                .map(|(argument, item)| match argument {
+                    Value::Vector(_) | Value::Matrix(_) => argument,
+                    _ => Evaluator::get_number(argument.clone(), item).map(Value::Number).unwrap_or(argument),
+                })
+                .collect();
+            return match (function.call)(&arguments) {
+                Err(Failure::Argument(index)) => {
+                    let position = for_both!(items[index], item => item.get_start());
+                    Err(Exception::create(Error::UnexpectedValue(arguments[index].get_type(), position), current_method!()))
+                },
+                Err(Failure::Shape(index, other)) => Err(Evaluator::get_mismatch(
+                    arguments[index].get_shape(), &[items[index]], arguments[other].get_shape(), &[items[other]],
+                )),
+                Err(Failure::Singular) => Err(Exception::create(Error::OutOfDomain(name.get_value(), start, next), current_method!())),
+                Ok(result) => Ok(result),
+            };
+        }
+
+        if let Some(function) = get_date_function(&name.get_value()) {
+            if arguments.len() != 1 {
+                let error = Error::InvalidArity(name.get_value(), String::from("1"), arguments.len(), start, next);
+                return Err(Exception::create(error, current_method!()));
+            }
+            return match (function.call)(&arguments[0]) {
+                None => {
+                    let position = for_both!(items[0], item => item.get_start());
+                    Err(Exception::create(Error::UnexpectedValue(arguments[0].get_type(), position), current_method!()))
+                },
+                Some(result) => Ok(result),
+            };
+        }
+
+        if let (Some(function), [Value::Decimal(argument)]) = (get_decimal_function(&name.get_value()), arguments.as_slice()) {
+            return match (function.call)(argument, &self.context) {
+                None => Err(Exception::create(Error::OutOfDomain(name.get_value(), start, next), current_method!())),
+                Some(result) => Ok(Value::Decimal(result)),
+            };
+        }
+
+        let complex_function = get_complex_function(&name.get_value());
+        let function = get_function(&name.get_value());
+        if let Some(complex_function) = complex_function {
+            if function.is_none() || arguments.iter().any(|argument| matches!(argument, Value::Complex(_))) {
+                if arguments.len() != 1 {
+                    let error = Error::InvalidArity(name.get_value(), String::from("1"), arguments.len(), start, next);
+                    return Err(Exception::create(error, current_method!()));
+                }
+                let argument = match Evaluator::get_complex(arguments.remove(0), items[0]) {
+                    Err(error) => return Err(Exception::relay(error, current_method!())),
+                    Ok(complex) => complex,
+                };
+                return match (complex_function.call)(argument) {
+                    None => Err(Exception::create(Error::OutOfDomain(name.get_value(), start, next), current_method!())),
+                    Some(result) => Ok(Value::from_complex(result)),
+                };
+            }
+        }
+
+        let function = match function {
+            None => return Err(Exception::create(Error::UnknownIdentifier(name.clone().into_owned()), current_method!())),
+            Some(function) => function,
+        };
+        if !function.arity.accepts(arguments.len()) {
+            let error = Error::InvalidArity(name.get_value(), function.arity.to_string(), arguments.len(), start, next);
+            return Err(Exception::create(error, current_method!()));
+        }
+
+        let mut numbers: Vec<f64> = vec![];
+        for (argument, item) in arguments.into_iter().zip(items) {
+            match Evaluator::get_number(argument, item) {
+                Err(error) => return Err(Exception::relay(error, current_method!())),
+                Ok(number) => numbers.push(number),
+            }
+        }
+        let result = match ((function.call)(&numbers), complex_function, numbers.as_slice()) {
+            (Some(result), _, _) => Some(Value::Number(result)),
+            // Out of the real domain, `sqrt(-1)` and `ln(-1)` still have a complex value.
+            (None, Some(complex_function), [number]) => {
+                (complex_function.call)(Complex64::new(*number, 0.0)).map(Value::from_complex)
+            },
+            (None, _, _) => None,
+        };
+        match result {
+            None => Err(Exception::create(Error::OutOfDomain(name.get_value(), start, next), current_method!())),
+            Some(result) => Ok(result),
+        }
+    }
+
+    fn evaluate_assign(&mut self, node: &Node) -> EvaluatorResult {
+        let value = node.children();
+        match (value.first(), value.last()) {
+            (Some(Left(name)), _) if is_reserved(&name.get_value()) => {
+                Err(Exception::create(Error::ReservedName(name.clone().into_owned()), current_method!()))
+            },
+            (Some(Left(name)), Some(item)) => match self.evaluate_item(item) {
+                Err(error) => Err(Exception::relay(error, current_method!())),
+                Ok(value) => {
+                    self.environment.set(&name.get_value(), value.clone());
+                    Ok(value)
+                }
+            },
+            _ => Err(Exception::create(Error::UnexpectedItem(Right(node.clone().into_owned())), current_method!())),
+        }
+    }
+
+    fn evaluate_define(&mut self, node: &Node) -> EvaluatorResult {
+        let value = node.children();
+        let (name, body) = match (value.first(), value.last()) {
+            (Some(Left(name)), Some(body)) => (name.clone(), body.clone()),
+            _ => return Err(Exception::create(Error::UnexpectedItem(Right(node.clone().into_owned())), current_method!())),
+        };
+        if is_reserved(&name.get_value()) {
+            return Err(Exception::create(Error::ReservedName(name.into_owned()), current_method!()));
+        }
+
+        let mut parameters: Vec<String> = vec![];
+        for item in &value[1..value.len() - 1] {
+            if let Left(token) = item {
+                if !token.is_identifier(None) { continue; }
+                if parameters.contains(&token.get_value()) {
+                    return Err(Exception::create(Error::DuplicateParameter(token.clone().into_owned()), current_method!()));
+                }
+                parameters.push(token.get_value());
+            }
+        }
+
+        let body = body.map_either(Token::into_owned, Node::into_owned);
+        self.environment.set_function(&name.get_value(), parameters.clone(), body);
+        Ok(Value::Function(name.get_value(), parameters))
+    }
+
+    /// Tells whether `evaluate_constant` finds the name before looking it up among the units.
+    fn is_constant(&self, name: &str) -> bool {
+        self.environment.get(name).is_some()
+            || (self.mode == Mode::Decimal && get_decimal_constant(name, &self.context).is_some())
+            || get_constant(name).is_some()
+    }
+
+    fn evaluate_constant(&mut self, node: &Node) -> EvaluatorResult {
+        match node.children().first() {
+            Some(Left(token)) => {
+                let name = token.get_value();
+                if let Some(value) = self.environment.get(&name) {
+                    return Ok(value);
+                }
+                if let (Mode::Decimal, Some(value)) = (self.mode, get_decimal_constant(&name, &self.context)) {
+                    return Ok(Value::Decimal(value));
+                }
+                if let Some(value) = get_constant(&name) {
+                    return Ok(value);
+                }
+                // A lone unit is one of it, so that `km/h` and `kg * m` are quantities.
+                if let Some(unit) = Unit::parse(&name) {
+                    return Ok(Value::Quantity(1.0, unit));
+                }
+                match get_currency(&name) {
+                    None => Err(Exception::create(Error::UnknownIdentifier(token.clone().into_owned()), current_method!())),
+                    Some(currency) => Ok(self.get_money(BigRational::one(), currency, None)),
+                }
+            },
+            _ => Err(Exception::create(Error::UnexpectedItem(Right(node.clone().into_owned())), current_method!())),
+        }
+    }
+
+    /// Reads a number written with a unit or a currency, as `3 km`, `30 €` or `$45`.
+    fn evaluate_quantity(&mut self, node: &Node) -> EvaluatorResult {
+        let value = node.children();
+        let (number, name) = match value {
+            [number, Left(name)] if name.is_identifier(None) => (number, name),
+            // The unit of `3 km^2` is raised to its power.
+            [number, Right(power)] => match power.children().first() {
+                Some(Right(constant)) => match constant.children().first() {
+                    Some(Left(name)) => (number, name),
+                    _ => return Err(Exception::create(Error::UnexpectedItem(Right(node.clone().into_owned())), current_method!())),
+                },
+                _ => return Err(Exception::create(Error::UnexpectedItem(Right(node.clone().into_owned())), current_method!())),
+            },
+            [Left(name), number] => (number, name),
+            _ => return Err(Exception::create(Error::UnexpectedItem(Right(node.clone().into_owned())), current_method!())),
+        };
+        // A variable or a constant after a number multiplies it rather than being read as a unit,
+        // as in `2 pi`, or `3 m` once `m` is assigned.
+        if !matches!(&value[1], Left(token) if token.is_number(None)) && self.is_constant(&name.get_value()) {
+            let operator = Token::Operator(Cow::Borrowed("*"), name.get_start(), name.get_start(), Trivia::default());
+            let operand = match &value[1] {
+                Left(name) => Right(Node::create("Constant", vec![Left(name.clone())])),
+                power => power.clone(),
+            };
+            return match self.evaluate_chain(&Node::create("Multiply", vec![number.clone(), Left(operator), operand])) {
+                Err(error) => Err(Exception::relay(error, current_method!())),
+                Ok(value) => Ok(value),
+            };
+        }
+
+        let magnitude = match self.evaluate_item(number) {
+            Err(error) => return Err(Exception::relay(error, current_method!())),
+            Ok(magnitude) => magnitude,
+        };
+        if let Right(_) = &value[1] {
+            return match (self.evaluate_unit(&value[1]), Evaluator::get_number(magnitude, number)) {
+                (Err(error), _) | (_, Err(error)) => Err(Exception::relay(error, current_method!())),
+                (Ok(unit), Ok(magnitude)) => Ok(Value::Quantity(magnitude, unit)),
+            };
+        }
+        let unit = match (Unit::parse(&name.get_value()), get_currency(&name.get_value())) {
+            (Some(unit), _) => unit,
+            (None, Some(currency)) => return match Evaluator::get_decimal_rational(magnitude, number) {
+                Err(error) => Err(Exception::relay(error, current_method!())),
+                Ok(amount) => Ok(self.get_money(amount, currency, None)),
+            },
+            (None, None) => return Err(Exception::create(Error::UnknownUnit(name.clone().into_owned()), current_method!())),
+        };
+        match Evaluator::get_number(magnitude, number) {
+            Err(error) => Err(Exception::relay(error, current_method!())),
+            Ok(magnitude) => Ok(Value::Quantity(magnitude, unit)),
+        }
+    }
+
+    /// Sums the parts of `3 h 25 min` or `5 ft 3 inch` in the unit of the first one, a sum of times
+    /// giving a duration.
+    fn evaluate_compound(&mut self, node: &Node) -> EvaluatorResult {
+        let mut total: Option<(f64, Unit)> = None;
+        for item in node.children() {
+            let (magnitude, unit) = match self.evaluate_item(item) {
+                Err(error) => return Err(Exception::relay(error, current_method!())),
+                Ok(Value::Quantity(magnitude, unit)) => (magnitude, unit),
+                Ok(value) => {
+                    let position = for_both!(item, item => item.get_start());
+                    return Err(Exception::create(Error::UnexpectedValue(value.get_type(), position), current_method!()));
+                },
+            };
+            total = match total {
+                None => Some((magnitude, unit)),
+                Some((_, first)) if first.dimension != unit.dimension => {
+                    return Err(Evaluator::get_incompatible(node, first.get_name(), unit.get_name()));
+                },
+                Some((sum, first)) => Some((sum + first.from_si_difference(unit.to_si_difference(magnitude)), first)),
+            };
+        }
+        match total {
+            None => Err(Exception::create(Error::UnexpectedItem(Right(node.clone().into_owned())), current_method!())),
+            Some((sum, unit)) if unit.dimension == Unit::parse("s").unwrap().dimension => {
+                match from_seconds(unit.to_si(sum)) {
+                    None => Err(Exception::create(Error::OutOfRange(node.get_code(), node.get_start()), current_method!())),
+                    Some(duration) => Ok(Value::Duration(duration)),
+                }
+            },
+            Some((sum, unit)) => Ok(Value::Quantity(sum, unit)),
+        }
+    }
+
+    /// Evaluates `[1, 2, 3]` to a vector, and to a matrix when its elements are vectors of the same
+    /// length or when its rows are separated by `;`.
+    fn evaluate_list(&mut self, node: &Node) -> EvaluatorResult {
+        let value = node.children();
+        let mut rows: Vec<Vec<(Value, &NodeItem)>> = vec![vec![]];
+        for item in value {
+            match item {
+                Left(token) if token.is_punctuation(Some(';')) => rows.push(vec![]),
+                Left(token) if token.is_punctuation(None) => continue,
+                item => match self.evaluate_item(item) {
+                    Err(error) => return Err(Exception::relay(error, current_method!())),
+                    Ok(element) => rows.last_mut().unwrap().push((element, item)),
+                },
+            }
+        }
+
+        // Written as `[[1, 2], [3, 4]]`, the rows are vectors.
+        if rows.len() == 1 && !rows[0].is_empty() && rows[0].iter().all(|(element, _)| matches!(element, Value::Vector(_))) {
+            let (first, first_item) = &rows[0][0];
+            let mut elements: Vec<Vec<f64>> = vec![];
+            for (element, item) in &rows[0] {
+                match element {
+                    Value::Vector(row) if elements.is_empty() || row.len() == elements[0].len() => elements.push(row.clone()),
+                    _ => return Err(Evaluator::get_mismatch(first.get_shape(), &[first_item], element.get_shape(), &[item])),
+                }
+            }
+            return Ok(Value::Matrix(Matrix::from_rows(elements).unwrap()));
+        }
+
+        let mut elements: Vec<Vec<f64>> = vec![];
+        for row in &rows {
+            let mut numbers: Vec<f64> = vec![];
+            for (element, item) in row {
+                match Evaluator::get_number(element.clone(), item) {
+                    Err(error) => return Err(Exception::relay(error, current_method!())),
+                    Ok(number) => numbers.push(number),
+                }
+            }
+            if !elements.is_empty() && numbers.len() != elements[0].len() {
+                let first: Vec<&NodeItem> = rows[0].iter().map(|(_, item)| *item).collect();
+                let items: Vec<&NodeItem> = row.iter().map(|(_, item)| *item).collect();
+                return Err(Evaluator::get_mismatch(elements[0].len().to_string(), &first, numbers.len().to_string(), &items));
+            }
+            elements.push(numbers);
+        }
+        match elements.len() {
+            1 => Ok(Value::Vector(elements.remove(0))),
+            _ => Ok(Value::Matrix(Matrix::from_rows(elements).unwrap())),
+        }
+    }
+
+    /// Converts `5 km to mi`, a plain number being converted like a dimensionless quantity.
+    fn evaluate_conversion(&mut self, node: &Node, value: Value) -> EvaluatorResult {
+        let (item, target) = match (node.children().first(), node.children().get(2)) {
+            (Some(item), Some(target)) => (item, target),
+            _ => return Err(Exception::create(Error::UnexpectedItem(Right(node.clone().into_owned())), current_method!())),
+        };
+        let currency = match Evaluator::get_inner(target) {
+            Right(node) if node.get_type() == "Constant" => get_currency(&node.get_code()),
+            _ => None,
+        };
+        match (value, currency) {
+            (Value::Money(money), Some(currency)) => match self.exchange(money, currency, node) {
+                Err(error) => Err(Exception::relay(error, current_method!())),
+                Ok(money) => Ok(Value::Money(money)),
+            },
+            (Value::Money(money), None) => {
+                let target = match target {
+                    Left(token) => token.get_value(),
+                    Right(node) => node.get_code(),
+                };
+                Err(Evaluator::get_incompatible(node, String::from(money.currency.code), target))
+            },
+            (value, Some(currency)) => {
+                let name = match value {
+                    Value::Quantity(_, unit) => unit.get_name(),
+                    _ => String::from("1"),
+                };
+                Err(Evaluator::get_incompatible(node, name, String::from(currency.code)))
+            },
+            (value, None) => self.convert_quantity(node, value, item, target),
+        }
+    }
+
+    fn convert_quantity(&mut self, node: &Node, value: Value, item: &NodeItem, target: &NodeItem) -> EvaluatorResult {
+        let (magnitude, unit) = match Evaluator::get_quantity(value, item) {
+            Err(error) => return Err(Exception::relay(error, current_method!())),
+            Ok((magnitude, unit)) => (magnitude, unit.unwrap_or_default()),
+        };
+        let target = match self.evaluate_unit(target) {
+            Err(error) => return Err(Exception::relay(error, current_method!())),
+            Ok(target) => target,
+        };
+        if unit.dimension != target.dimension {
+            return Err(Evaluator::get_incompatible(node, unit.get_name(), target.get_name()));
+        }
+        Ok(Value::Quantity(target.from_si(unit.to_si(magnitude)), target))
+    }
+
+    /// Reads the target of a conversion, a product of units with integral powers as `km/h`.
+    fn evaluate_unit(&mut self, item: &NodeItem) -> Result<Unit, Exception> {
+        let node = match Evaluator::get_inner(item) {
+            Left(token) => return Err(Exception::create(Error::UnexpectedToken(token.clone().into_owned()), current_method!())),
+            Right(node) => node,
+        };
+        let value = node.children();
+        match (node.get_type().as_str(), value.first(), value.get(2)) {
+            ("Constant", Some(Left(name)), _) => match Unit::parse(&name.get_value()) {
+                None => Err(Exception::create(Error::UnknownUnit(name.clone().into_owned()), current_method!())),
+                Some(unit) => Ok(unit),
+            },
+            // A chain as `kg*m/s/s` is read in a loop, like in `evaluate_chain`.
+            ("Multiply" | "Divide", Some(_), Some(_)) => {
+                let mut chain = vec![node];
+                while let Some(Right(left)) = chain.last().unwrap().children().first() {
+                    if !matches!(left.get_type().as_str(), "Multiply" | "Divide") {
+                        break;
+                    }
+                    chain.push(left);
+                }
+                let mut unit = match self.evaluate_unit(&chain.last().unwrap().children()[0]) {
+                    Err(error) => return Err(Exception::relay(error, current_method!())),
+                    Ok(unit) => unit,
+                };
+                while let Some(node) = chain.pop() {
+                    unit = match node.children().get(2).map(|right| self.evaluate_unit(right)) {
+                        None => return Err(Exception::create(Error::UnexpectedItem(Right(node.clone().into_owned())), current_method!())),
+                        Some(Err(error)) => return Err(Exception::relay(error, current_method!())),
+                        Some(Ok(other)) if node.get_type() == "Multiply" => unit.multiply(&other),
+                        Some(Ok(other)) => unit.multiply(&other.power(-1)),
+                    };
+                }
+                Ok(unit)
+            },
+            ("Power", Some(left), Some(right)) => {
+                let unit = match self.evaluate_unit(left) {
+                    Err(error) => return Err(Exception::relay(error, current_method!())),
+                    Ok(unit) => unit,
+                };
+                let exponent = match self.evaluate_item(right) {
+                    Err(error) => return Err(Exception::relay(error, current_method!())),
+                    Ok(exponent) => exponent,
+                };
+                match Evaluator::get_integer(exponent, right) {
+                    Err(error) => Err(Exception::relay(error, current_method!())),
+                    Ok(exponent) => match exponent.to_i32() {
+                        None => {
+                            let position = for_both!(right, item => item.get_start());
+                            Err(Exception::create(Error::OutOfRange(exponent.to_string(), position), current_method!()))
+                        },
+                        Some(exponent) => Ok(unit.power(exponent)),
+                    },
+                }
+            },
+            _ => Err(Exception::create(Error::UnexpectedItem(item.clone().map_either(Token::into_owned, Node::into_owned)), current_method!())),
+        }
+    }
+
+    /// Looks through the parentheses around an item, to the expression they group.
+    fn get_inner<'b, 'a>(item: &'b NodeItem<'a>) -> &'b NodeItem<'a> {
+        let mut item = item;
+        while let Right(node) = item {
+            match node.children().get(1) {
+                Some(inner) if node.get_type() == "Group" => item = inner,
+                _ => break,
+            }
+        }
+        item
+    }
+
+    fn get_number(value: Value, item: &NodeItem) -> Result<f64, Exception> {
+        match value {
+            Value::Number(number) => Ok(number),
+            Value::Integer(integer) | Value::Fixed(integer, _) => Ok(integer.to_f64().unwrap_or(f64::NAN)),
+            Value::Rational(rational) => Ok(rational.to_f64().unwrap_or(f64::NAN)),
+            Value::Decimal(decimal) => Ok(decimal.to_rational().to_f64().unwrap_or(f64::NAN)),
+            // Angles are dimensionless, `sin(30 deg)` being the sine of their value in radians.
+            Value::Quantity(magnitude, unit) if unit.dimension.is_none() => Ok(unit.to_si(magnitude)),
+            _ => {
+                let position = for_both!(item, item => item.get_start());
+                Err(Exception::create(Error::UnexpectedValue(value.get_type(), position), current_method!()))
+            }
+        }
+    }
+
+    /// Separates the magnitude of a quantity from its unit, other numbers having none.
+    fn get_quantity(value: Value, item: &NodeItem) -> Result<(f64, Option<Unit>), Exception> {
+        match value {
+            Value::Quantity(magnitude, unit) => Ok((magnitude, Some(unit))),
+            Value::Duration(duration) => Ok((get_seconds(&duration), Unit::parse("s"))),
+            value => match Evaluator::get_number(value, item) {
+                Err(error) => Err(Exception::relay(error, current_method!())),
+                Ok(number) => Ok((number, None)),
+            },
+        }
+    }
+
+    fn get_integer(value: Value, item: &NodeItem) -> Result<BigInt, Exception> {
+        match value {
+            Value::Integer(integer) | Value::Fixed(integer, _) => Ok(integer),
+            Value::Number(number) if number.fract() == 0.0 => Ok(BigInt::from_f64(number).unwrap()),
+            Value::Rational(rational) if rational.is_integer() => Ok(rational.to_integer()),
+            Value::Decimal(decimal) if decimal.to_rational().is_integer() => Ok(decimal.to_rational().to_integer()),
+            _ => {
+                let position = for_both!(item, item => item.get_start());
+                Err(Exception::create(Error::UnexpectedValue(value.get_type(), position), current_method!()))
+            }
+        }
+    }
+
+    fn get_complex(value: Value, item: &NodeItem) -> Result<Complex64, Exception> {
+        match value {
+            Value::Complex(complex) => Ok(complex),
+            value => match Evaluator::get_number(value, item) {
+                Err(error) => Err(Exception::relay(error, current_method!())),
+                Ok(number) => Ok(Complex64::new(number, 0.0)),
+            },
+        }
+    }
+
+    /// Reads an integer, or a float small enough to hold one exactly.
+    fn get_exact(value: &Value) -> Option<BigInt> {
+        match value {
+            Value::Integer(integer) => Some(integer.clone()),
+            Value::Number(number) if number.fract() == 0.0 && number.abs() < i64::MAX as f64 => BigInt::from_f64(*number),
+            _ => None,
+        }
+    }
+
+    /// Gives a float for integers that a float holds exactly, and keeps the larger ones exact.
+    fn promote(integer: BigInt) -> Value {
+        match integer.to_i64() {
+            Some(small) if small.unsigned_abs() <= MAX_SAFE_INTEGER => Value::Number(small as f64),
+            _ => Value::Integer(integer),
+        }
+    }
+
+    /// Reads a number exactly, a float being taken as the decimal it is written as, `0.1` for `0.1`.
+    fn get_decimal_rational(value: Value, item: &NodeItem) -> Result<BigRational, Exception> {
+        if let Some(rational) = Evaluator::get_rational(&value) {
+            return Ok(rational);
+        }
+        let number = match Evaluator::get_number(value, item) {
+            Err(error) => return Err(Exception::relay(error, current_method!())),
+            Ok(number) => number,
+        };
+        match Value::parse_rational(&number.abs().to_string()) {
+            None => {
+                let position = for_both!(item, item => item.get_start());
+                Err(Exception::create(Error::OutOfRange(number.to_string(), position), current_method!()))
+            },
+            Some(rational) if number.is_sign_negative() => Ok(-rational),
+            Some(rational) => Ok(rational),
+        }
+    }
+
+    fn get_rational(value: &Value) -> Option<BigRational> {
+        match value {
+            Value::Rational(rational) => Some(rational.clone()),
+            Value::Integer(integer) => Some(BigRational::from_integer(integer.clone())),
+            Value::Decimal(decimal) => Some(decimal.to_rational()),
+            _ => None,
+        }
+    }
+
+    fn get_fixed(value: Value, integer_type: IntegerType, item: &NodeItem) -> Result<BigInt, Exception> {
+        match Evaluator::get_integer(value, item) {
+            Err(error) => Err(Exception::relay(error, current_method!())),
+            Ok(integer) if integer_type.contains(&integer) => Ok(integer),
+            Ok(integer) => {
+                let position = for_both!(item, item => item.get_start());
+                Err(Exception::create(Error::OutOfRange(integer.to_string(), position), current_method!()))
+            },
+        }
+    }
+
+    fn evaluate_number(&self, token: &Token, negative: bool) -> EvaluatorResult {
+        let text = token.get_value();
+        if is_date_time(&text) {
+            return match parse_date_time(&text) {
+                None => Err(Exception::create(Error::InvalidDate(text, token.get_start()), current_method!())),
+                Some(value) if negative => {
+                    Err(Exception::create(Error::UnexpectedValue(value.get_type(), token.get_start()), current_method!()))
+                },
+                Some(value) => Ok(value),
+            };
+        }
+        let (digits, suffix) = IntegerType::split_suffix(&text);
+        let sign = if negative { -1 } else { 1 };
+        if let Some(imaginary) = digits.strip_suffix(['i', 'j']) {
+            return match imaginary.parse::<f64>() {
+                Err(_) => Err(Exception::create(Error::InvalidNumber(token.clone().into_owned()), current_method!())),
+                Ok(number) => Ok(Value::Complex(Complex64::new(0.0, number * sign as f64))),
+            };
+        }
+        let integer = Value::parse_integer(digits).or_else(|| {
+            digits.parse::<f64>().ok().filter(|number| number.fract() == 0.0).and_then(BigInt::from_f64)
+        }).map(|integer| integer * sign);
+        let value = match (suffix, self.mode) {
+            // The sign is applied first, so that `-1` is out of the range of `u8` and not `1`.
+            (Some(integer_type), _) | (None, Mode::Fixed(integer_type)) => match integer {
+                Some(integer) => match self.try_fit(integer, integer_type) {
+                    None => {
+                        let text = if negative { format!("-{}", text) } else { text };
+                        return Err(Exception::create(Error::OutOfRange(text, token.get_start()), current_method!()));
+                    },
+                    Some(integer) => Some(Value::Fixed(integer, integer_type)),
+                },
+                None => None,
+            },
+            (None, Mode::Integer) => integer.map(Value::Integer),
+            (None, Mode::Rational) => Value::parse_rational(digits)
+                .map(|rational| Value::Rational(if negative { -rational } else { rational })),
+            (None, Mode::Decimal) => Value::parse_rational(digits)
+                .map(|rational| if negative { -rational } else { rational })
+                .map(|rational| Value::Decimal(Decimal::from_rational(&rational, &self.context))),
+            (None, Mode::Float) => match Value::parse_integer(digits) {
+                Some(integer) => Some(Evaluator::promote(integer * sign)),
+                None => digits.parse::<f64>().ok().map(|number| Value::Number(number * sign as f64)),
+            },
+        };
+        match value {
+            // A number written correctly can only fail to be read exactly by having too large an exponent.
+            None if suffix.is_none() && matches!(self.mode, Mode::Rational | Mode::Decimal)
+                && digits.replace('_', "").parse::<f64>().is_ok() => {
+                let text = if negative { format!("-{}", text) } else { text };
+                Err(Exception::create(Error::OutOfRange(text, token.get_start()), current_method!()))
+            },
+            None => Err(Exception::create(Error::InvalidNumber(token.clone().into_owned()), current_method!())),
+            Some(value) => Ok(value),
+        }
+    }
+
+    fn evaluate_token(&mut self, token: &Token) -> EvaluatorResult {
+        if token.is_number(None) {
+            self.evaluate_number(token, false)
+        } else {
+            Err(Exception::create(Error::UnexpectedToken(token.clone().into_owned()), current_method!()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::*;
+    use crate::parser::*;
+
+    fn run(evaluator: &mut Evaluator, text: &str) -> Result<String, Exception> {
+        let mut parser = Parser::from(Lexer::from(text).lex()?);
+        parser.set_xor_caret(matches!(evaluator.get_mode(), Mode::Integer | Mode::Fixed(_)));
+        let node = parser.parse()?;
+        Ok(evaluator.evaluate(&node)?.to_string())
+    }
+
+    fn evaluate(text: &str) -> Result<String, Exception> {
+        run(&mut Evaluator::default(), text)
+    }
+
+    #[test]
+    fn evaluates_through_parentheses() {
+        assert_eq!(evaluate("(1 + 2) * 3").unwrap(), "9");
+        assert_eq!(evaluate("((2))^3").unwrap(), "8");
+        assert_eq!(evaluate("200 + (10%)").unwrap(), "220");
+        assert_eq!(evaluate("(5 km) to (m)").unwrap(), "5000 m");
+        assert_eq!(evaluate("(1 < 2) ? (10) : 20").unwrap(), "10");
+    }
+
+    #[test]
+    fn computes_remainders_of_negative_divisors() {
+        assert_eq!(evaluate("7 % (-2)").unwrap(), "1");
+        assert_eq!(evaluate("-7 % 2").unwrap(), "-1");
+        let mut evaluator = Evaluator::default();
+        evaluator.set_remainder(Remainder::Floored);
+        assert_eq!(run(&mut evaluator, "7 % (-3)").unwrap(), "-2");
+        assert_eq!(run(&mut evaluator, "200 + 10% - 5").unwrap(), "215");
+        assert_eq!(run(&mut evaluator, "7 % -3").unwrap(), run(&mut evaluator, "7% - 3").unwrap());
+    }
+
+    #[test]
+    fn keeps_integers_beyond_floats_exact() {
+        assert_eq!(evaluate("9007199254740993").unwrap(), "9007199254740993");
+        assert_eq!(evaluate("2^53 + 1").unwrap(), "9007199254740993");
+        assert_eq!(evaluate("2^62 + 1").unwrap(), "4611686018427387905");
+        assert_eq!(evaluate("2^63 - 1").unwrap(), "9223372036854775807");
+        assert_eq!(evaluate("-(2^53 + 1)").unwrap(), "-9007199254740993");
+        assert_eq!(evaluate("2^53").unwrap(), "9007199254740992");
+    }
+
+    #[test]
+    fn checks_the_range_of_signed_literals() {
+        let mut evaluator = Evaluator::default();
+        evaluator.set_mode(Mode::Fixed(IntegerType::parse("u8").unwrap()));
+        let error = run(&mut evaluator, "-1").unwrap_err();
+        assert!(matches!(*error.error, Error::OutOfRange(ref text, _) if text == "-1"));
+        evaluator.set_overflow(Overflow::Wrapping);
+        assert_eq!(run(&mut evaluator, "-1").unwrap(), "255");
+        assert_eq!(run(&mut evaluator, "300").unwrap(), "44");
+        evaluator.set_overflow(Overflow::Saturating);
+        assert_eq!(run(&mut evaluator, "-1").unwrap(), "0");
+        evaluator.set_mode(Mode::Fixed(IntegerType::parse("i8").unwrap()));
+        evaluator.set_overflow(Overflow::Error);
+        assert_eq!(run(&mut evaluator, "-128").unwrap(), "-128");
+    }
+
+    #[test]
+    fn reserves_built_in_names() {
+        for text in ["pi = 3", "pi(x) = x", "sqrt = 2", "sqrt(x) = 2", "year(x) = x"] {
+            assert!(matches!(*evaluate(text).unwrap_err().error, Error::ReservedName(_)), "{}", text);
+        }
+        let mut evaluator = Evaluator::default();
+        assert_eq!(run(&mut evaluator, "x = 3").unwrap(), "3");
+        assert_eq!(run(&mut evaluator, "f(y) = y + x").unwrap(), "f(y)");
+        assert_eq!(run(&mut evaluator, "f(1)").unwrap(), "4");
+    }
+
+    #[test]
+    fn reads_dates_and_conditionals_apart() {
+        assert_eq!(evaluate("1 < 2 ? 10:20").unwrap(), "10");
+        assert_eq!(evaluate("1 > 2 ? 10:20").unwrap(), "20");
+        assert_eq!(evaluate("isoweek(2027-01-01)").unwrap(), "53");
+        assert_eq!(evaluate("isoweek(2026-10-17)").unwrap(), evaluate("week(2026-10-17)").unwrap());
+    }
+
+    #[test]
+    fn multiplies_constants_and_variables_after_numbers() {
+        assert_eq!(evaluate("2 pi").unwrap(), evaluate("2 * pi").unwrap());
+        assert_eq!(evaluate("3 km").unwrap(), "3 km");
+        let mut evaluator = Evaluator::default();
+        assert_eq!(run(&mut evaluator, "x = 4").unwrap(), "4");
+        assert_eq!(run(&mut evaluator, "3 x").unwrap(), "12");
+        assert_eq!(run(&mut evaluator, "m = 5").unwrap(), "5");
+        assert_eq!(run(&mut evaluator, "3 m").unwrap(), "15");
+    }
+
+    #[test]
+    fn keeps_powers_and_remainders_of_rationals_exact() {
+        let mut evaluator = Evaluator::default();
+        evaluator.set_mode(Mode::Rational);
+        let mut rational = |text: &str| run(&mut evaluator, text);
+        assert_eq!(rational("(1/2)^3").unwrap(), rational("1/8").unwrap());
+        assert_eq!(rational("(2/3)^-2").unwrap(), rational("9/4").unwrap());
+        assert_eq!(rational("7/2 % 1/3").unwrap(), rational("1/6").unwrap());
+        assert_eq!(rational("-7/2 % (1/3)").unwrap(), rational("-1/6").unwrap());
+        assert_eq!(rational("0.7 % 0.2").unwrap(), rational("0.1").unwrap());
+        assert!(matches!(*rational("0^-1").unwrap_err().error, Error::DivisionByZero(_)));
+        assert!(matches!(*rational("1/2 % 0").unwrap_err().error, Error::DivisionByZero(_)));
+        evaluator.set_remainder(Remainder::Floored);
+        assert_eq!(run(&mut evaluator, "-7/2 % (1/3)").unwrap(), run(&mut evaluator, "1/6").unwrap());
+        assert_eq!(run(&mut evaluator, "7/2 % (-1/3)").unwrap(), run(&mut evaluator, "-1/6").unwrap());
+    }
+
+    #[test]
+    fn reads_numbers_as_conditions() {
+        assert_eq!(evaluate("2 && 3").unwrap(), "true");
+        assert_eq!(evaluate("0 || 0.0").unwrap(), "false");
+        assert_eq!(evaluate("!0").unwrap(), "true");
+        assert_eq!(evaluate("1 - 1 ? 10 : 20").unwrap(), "20");
+        assert_eq!(evaluate("!1 == 2").unwrap(), "true");
+        assert_eq!(evaluate("!(1 == 1) || 2 < 1").unwrap(), "false");
+        assert!(matches!(*evaluate("2026-10-17 && 1").unwrap_err().error, Error::UnexpectedValue(..)));
+    }
+
+    #[test]
+    fn evaluates_long_chains() {
+        assert_eq!(evaluate(&format!("1{}", " + 1".repeat(50_000))).unwrap(), "50001");
+        assert_eq!(evaluate(&format!("1{}", " * 1".repeat(50_000))).unwrap(), "1");
+        assert_eq!(evaluate(&format!("1 m{}", " * 1".repeat(50_000))).unwrap(), evaluate("1 m").unwrap());
+    }
+
+    #[test]
+    fn computes_arithmetic_by_precedence() {
+        assert_eq!(evaluate("1 + 2 * 3").unwrap(), "7");
+        assert_eq!(evaluate("10 - 4 - 3").unwrap(), "3");
+        assert_eq!(evaluate("7 / 2").unwrap(), "3.5");
+        assert_eq!(evaluate("2 * 3 / 4").unwrap(), "1.5");
+        let error = evaluate("1 / 0").unwrap_err();
+        assert!(matches!(*error.error, Error::DivisionByZero(position) if position.cursor == 4));
+    }
+
+    #[test]
+    fn applies_unary_signs() {
+        assert_eq!(evaluate("-2").unwrap(), "-2");
+        assert_eq!(evaluate("+5").unwrap(), "5");
+        assert_eq!(evaluate("--2").unwrap(), "2");
+        assert_eq!(evaluate("2 - -3").unwrap(), "5");
+        assert_eq!(evaluate("-2^2").unwrap(), "-4");
+        assert_eq!(evaluate("-(1 + 2) * 3").unwrap(), "-9");
+    }
+
+    #[test]
+    fn reads_scientific_notation() {
+        assert_eq!(evaluate("1.5e3").unwrap(), "1500");
+        assert_eq!(evaluate("2E-2").unwrap(), "0.02");
+        assert_eq!(evaluate("3e2 * 2e-1").unwrap(), "60");
+    }
+
+    #[test]
+    fn raises_powers_from_the_right() {
+        assert_eq!(evaluate("2^3^2").unwrap(), "512");
+        assert_eq!(evaluate("(2^3)^2").unwrap(), "64");
+        assert_eq!(evaluate("2 ** 10").unwrap(), "1024");
+        assert_eq!(evaluate("2^-1").unwrap(), "0.5");
+        assert_eq!(evaluate("4^0.5").unwrap(), "2");
+    }
+
+    #[test]
+    fn applies_percentages() {
+        assert_eq!(evaluate("50%").unwrap(), "0.5");
+        assert_eq!(evaluate("200 + 10%").unwrap(), "220");
+        assert_eq!(evaluate("200 - 10%").unwrap(), "180");
+        assert_eq!(evaluate("200 * 10%").unwrap(), "20");
+        assert_eq!(evaluate("7 % 3").unwrap(), "1");
+        assert!(matches!(*evaluate("7 % 0").unwrap_err().error, Error::DivisionByZero(_)));
+    }
+
+    #[test]
+    fn reads_named_constants() {
+        assert_eq!(evaluate("pi").unwrap(), "3.141592653589793");
+        assert_eq!(evaluate("tau / 2 == pi").unwrap(), "true");
+        assert_eq!(evaluate("e").unwrap(), "2.718281828459045");
+        assert_eq!(evaluate("-inf").unwrap(), "-inf");
+        let error = evaluate("foo").unwrap_err();
+        assert!(matches!(*error.error, Error::UnknownIdentifier(ref token) if token.get_value() == "foo"));
+    }
+
+    #[test]
+    fn calls_built_in_functions() {
+        assert_eq!(evaluate("sqrt(16)").unwrap(), "4");
+        assert_eq!(evaluate("max(1, 5, 3) + min(2, 4)").unwrap(), "7");
+        assert_eq!(evaluate("atan2(0, 1)").unwrap(), "0");
+        assert!(matches!(*evaluate("sqrt(1, 2)").unwrap_err().error, Error::InvalidArity(..)));
+        assert!(matches!(*evaluate("ln(0)").unwrap_err().error, Error::OutOfDomain(..)));
+    }
+
+    #[test]
+    fn keeps_variables_between_evaluations() {
+        let mut evaluator = Evaluator::default();
+        assert_eq!(run(&mut evaluator, "x = 5").unwrap(), "5");
+        assert_eq!(run(&mut evaluator, "x * 2").unwrap(), "10");
+        assert_eq!(run(&mut evaluator, "x = x + 1").unwrap(), "6");
+        assert_eq!(run(&mut evaluator, "x").unwrap(), "6");
+        assert!(matches!(*evaluate("x").unwrap_err().error, Error::UnknownIdentifier(_)));
+        evaluator.get_environment().clear();
+        assert!(run(&mut evaluator, "x").is_err());
+    }
+
+    #[test]
+    fn calls_user_defined_functions() {
+        let mut evaluator = Evaluator::default();
+        assert_eq!(run(&mut evaluator, "f(x, y) = x^2 + y").unwrap(), "f(x, y)");
+        assert_eq!(run(&mut evaluator, "f(3, 1)").unwrap(), "10");
+        assert!(matches!(*run(&mut evaluator, "f(1)").unwrap_err().error, Error::InvalidArity(..)));
+        assert_eq!(run(&mut evaluator, "fact(n) = n <= 1 ? 1 : n * fact(n - 1)").unwrap(), "fact(n)");
+        assert_eq!(run(&mut evaluator, "fact(5)").unwrap(), "120");
+        // A function doesn't see the parameters of its caller.
+        assert_eq!(run(&mut evaluator, "g(a) = h()").unwrap(), "g(a)");
+        assert_eq!(run(&mut evaluator, "h() = a").unwrap(), "h()");
+        assert!(matches!(*run(&mut evaluator, "g(1)").unwrap_err().error, Error::UnknownIdentifier(_)));
+        evaluator.set_max_depth(10);
+        assert_eq!(run(&mut evaluator, "loop(n) = loop(n + 1)").unwrap(), "loop(n)");
+        assert!(matches!(*run(&mut evaluator, "loop(0)").unwrap_err().error, Error::RecursionLimit(_, 10, _, _)));
+    }
+
+    #[test]
+    fn compares_and_short_circuits() {
+        assert_eq!(evaluate("1 < 2").unwrap(), "true");
+        assert_eq!(evaluate("2 <= 2 && 3 >= 4").unwrap(), "false");
+        assert_eq!(evaluate("1 + 1 == 2").unwrap(), "true");
+        assert_eq!(evaluate("3 != 3").unwrap(), "false");
+        assert_eq!(evaluate("false && unknown").unwrap(), "false");
+        assert_eq!(evaluate("true || unknown").unwrap(), "true");
+        assert!(evaluate("true && unknown").is_err());
+        assert_eq!(evaluate("1 > 2 ? 1 : 2 > 1 ? 3 : 4").unwrap(), "3");
+    }
+
+    #[test]
+    fn computes_bitwise_operations_in_programmer_mode() {
+        assert_eq!(evaluate("0xff").unwrap(), "255");
+        assert_eq!(evaluate("0b1010 + 0o17").unwrap(), "25");
+        assert_eq!(evaluate("0xff & 0x0f").unwrap(), "15");
+        assert_eq!(evaluate("1 << 4").unwrap(), "16");
+        let mut evaluator = Evaluator::default();
+        evaluator.set_mode(Mode::Integer);
+        assert_eq!(run(&mut evaluator, "6 ^ 3").unwrap(), "5");
+        assert_eq!(run(&mut evaluator, "6 | 3").unwrap(), "7");
+        assert_eq!(run(&mut evaluator, "7 / 2").unwrap(), "3");
+        assert_eq!(run(&mut evaluator, "~0").unwrap(), "-1");
+        assert_eq!(run(&mut evaluator, "2 ** 100").unwrap(), "1267650600228229401496703205376");
+        assert!(matches!(*run(&mut evaluator, "0.5").unwrap_err().error, Error::InvalidNumber(_)));
+    }
+
+    #[test]
+    fn overflows_fixed_width_integers_by_policy() {
+        let mut evaluator = Evaluator::default();
+        evaluator.set_mode(Mode::Fixed(IntegerType::parse("u8").unwrap()));
+        assert!(matches!(*run(&mut evaluator, "255 + 1").unwrap_err().error, Error::Overflow(..)));
+        assert!(matches!(*run(&mut evaluator, "1u8 + 1i8").unwrap_err().error, Error::UnexpectedValue(..)));
+        evaluator.set_overflow(Overflow::Wrapping);
+        assert_eq!(run(&mut evaluator, "255 + 1").unwrap(), "0");
+        assert_eq!(run(&mut evaluator, "0 - 1").unwrap(), "255");
+        evaluator.set_overflow(Overflow::Saturating);
+        assert_eq!(run(&mut evaluator, "200 * 2").unwrap(), "255");
+        assert_eq!(run(&mut evaluator, "127i8 + 1i8").unwrap(), "127");
+    }
+
+    #[test]
+    fn adds_decimals_exactly_in_rational_mode() {
+        let mut evaluator = Evaluator::default();
+        evaluator.set_mode(Mode::Rational);
+        assert_eq!(run(&mut evaluator, "0.1 + 0.2").unwrap(), "3/10");
+        assert_eq!(run(&mut evaluator, "0.1 + 0.2 == 0.3").unwrap(), "true");
+        assert_eq!(run(&mut evaluator, "2/3 + 1/6").unwrap(), "5/6");
+        assert_eq!(run(&mut evaluator, "4/2").unwrap(), "2");
+        assert!(matches!(*run(&mut evaluator, "1/(1/2 - 0.5)").unwrap_err().error, Error::DivisionByZero(_)));
+        assert_eq!(evaluate("0.1 + 0.2 == 0.3").unwrap(), "false");
+    }
+
+    #[test]
+    fn rounds_to_the_precision_of_decimal_mode() {
+        let mut evaluator = Evaluator::default();
+        evaluator.set_mode(Mode::Decimal);
+        assert_eq!(run(&mut evaluator, "0.1 + 0.2").unwrap(), "0.3");
+        evaluator.set_precision(10);
+        assert_eq!(run(&mut evaluator, "1/3").unwrap(), "0.3333333333");
+        assert_eq!(run(&mut evaluator, "sqrt(2)").unwrap(), "1.414213562");
+        assert_eq!(run(&mut evaluator, "pi").unwrap(), "3.141592654");
+        evaluator.set_rounding(Rounding::Down);
+        assert_eq!(run(&mut evaluator, "2/3").unwrap(), "0.6666666666");
+        assert!(matches!(*run(&mut evaluator, "sqrt(-1)").unwrap_err().error, Error::OutOfDomain(..)));
+    }
+
+    #[test]
+    fn computes_factorials_and_powers_exactly() {
+        assert_eq!(evaluate("20!").unwrap(), "2432902008176640000");
+        assert_eq!(evaluate("25!").unwrap(), "15511210043330985984000000");
+        assert_eq!(evaluate("2^100").unwrap(), "1267650600228229401496703205376");
+        assert_eq!(evaluate("2^100 / 2^99").unwrap(), "2");
+        assert_eq!(evaluate("0!").unwrap(), "1");
+        assert!(matches!(*evaluate("(-1)!").unwrap_err().error, Error::OutOfDomain(..)));
+        assert!(matches!(*evaluate("10001!").unwrap_err().error, Error::OutOfDomain(..)));
+    }
+
+    #[test]
+    fn computes_with_complex_numbers() {
+        assert_eq!(evaluate("sqrt(-1)").unwrap(), "i");
+        assert_eq!(evaluate("i^2").unwrap(), "-1");
+        assert_eq!(evaluate("(1+2i)*(3-i)").unwrap(), "5+5i");
+        assert_eq!(evaluate("abs(3+4i)").unwrap(), "5");
+        assert_eq!(evaluate("(1+i) / (1-i)").unwrap(), "i");
+        assert!(matches!(*evaluate("1i / 0").unwrap_err().error, Error::DivisionByZero(_)));
+        assert!(matches!(*evaluate("1i < 2").unwrap_err().error, Error::UnexpectedValue(..)));
+    }
+
+    #[test]
+    fn computes_with_units() {
+        assert_eq!(evaluate("3 km + 200 m").unwrap(), "3.2 km");
+        assert_eq!(evaluate("9.81 m/s^2 * 70 kg").unwrap(), "686.7 N");
+        assert_eq!(evaluate("5 km to m").unwrap(), "5000 m");
+        assert_eq!(evaluate("2 h in min").unwrap(), "120 min");
+        assert_eq!(evaluate("100 °C to K").unwrap(), "373.15 K");
+        let error = evaluate("3 km + 2 kg").unwrap_err();
+        assert!(matches!(*error.error, Error::IncompatibleUnits(_, _, start, next) if start.cursor == 0 && next.cursor == 11));
+        assert!(matches!(*evaluate("3 km to kg").unwrap_err().error, Error::IncompatibleUnits(..)));
+        assert!(matches!(*evaluate("3 furlongs").unwrap_err().error, Error::UnknownUnit(_)));
+    }
+
+    #[test]
+    fn converts_currencies_with_the_given_rates() {
+        let mut evaluator = Evaluator::default();
+        assert_eq!(run(&mut evaluator, "$45 + 5 $").unwrap(), "50.00 USD");
+        assert_eq!(run(&mut evaluator, "0.1 € + 0.2 €").unwrap(), "0.30 EUR");
+        assert!(matches!(*run(&mut evaluator, "30 € to $").unwrap_err().error, Error::UnknownRate(..)));
+        evaluator.set_rates(Rates::parse(r#"{"base": "EUR", "rates": {"USD": "1.25"}}"#).unwrap());
+        assert_eq!(run(&mut evaluator, "30 € to $").unwrap(), "37.50 USD");
+        assert_eq!(run(&mut evaluator, "10 EUR + 5 USD").unwrap(), "14.00 EUR");
+        assert_eq!(run(&mut evaluator, "$10 * 3").unwrap(), "30.00 USD");
+    }
+
+    #[test]
+    fn computes_with_dates_and_durations() {
+        assert_eq!(evaluate("2026-10-17 + 90 days").unwrap(), "2027-01-15");
+        assert_eq!(evaluate("2026-12-25 - 2026-10-17").unwrap(), "69d");
+        assert_eq!(evaluate("18:30 - 09:15").unwrap(), "9h 15min");
+        assert_eq!(evaluate("3h 25min * 4").unwrap(), "13h 40min");
+        assert_eq!(evaluate("2026-10-17T18:30 + 6 h").unwrap(), "2026-10-18T00:30");
+        assert_eq!(evaluate("weekday(2026-10-17)").unwrap(), "6");
+        assert_eq!(evaluate("2026-10-17 < 2026-10-18").unwrap(), "true");
+        let error = evaluate("1 + 2026-02-30").unwrap_err();
+        assert!(matches!(*error.error, Error::InvalidDate(ref text, position) if text == "2026-02-30" && position.cursor == 4));
+    }
+
+    #[test]
+    fn computes_with_vectors_and_matrices() {
+        assert_eq!(evaluate("[1, 2] + [3, 4]").unwrap(), "[4, 6]");
+        assert_eq!(evaluate("[1, 2, 3] * 2").unwrap(), "[2, 4, 6]");
+        assert_eq!(evaluate("[[1, 2], [3, 4]] * [[5, 6], [7, 8]]").unwrap(), "[[19, 22], [43, 50]]");
+        assert_eq!(evaluate("[1, 2; 3, 4]").unwrap(), "[[1, 2], [3, 4]]");
+        assert_eq!(evaluate("[1, 2] .* [3, 4]").unwrap(), "[3, 8]");
+        assert_eq!(evaluate("det([[1, 2], [3, 4]])").unwrap(), "-2");
+        assert_eq!(evaluate("dot([1, 2, 3], [4, 5, 6])").unwrap(), "32");
+        assert_eq!(evaluate("[[2, 0], [0, 4]] \\ [2, 4]").unwrap(), "[1, 1]");
+        assert!(evaluate("[1, 2] + [1, 2, 3]").is_err());
+        assert!(evaluate("[[1, 2], [3]]").is_err());
+    }
+
+    #[test]
+    fn skips_whitespace_anywhere() {
+        assert_eq!(evaluate("  1 +\n 2\t").unwrap(), "3");
+        assert_eq!(evaluate("\tsqrt ( 16 ) ").unwrap(), "4");
+        assert_eq!(evaluate(" 3   km  to\nm ").unwrap(), "3000 m");
+        assert_eq!(evaluate(" $ 45 ").unwrap(), "45.00 USD");
+    }
+
+    #[test]
+    fn points_errors_at_whole_nodes() {
+        let error = evaluate("1 + (3 km + 2 kg) ").unwrap_err();
+        assert!(matches!(*error.error, Error::IncompatibleUnits(_, _, start, next) if start.cursor == 5 && next.cursor == 16));
+        let mut evaluator = Evaluator::default();
+        run(&mut evaluator, "f(x) = x + 1").unwrap();
+        let error = run(&mut evaluator, "2 * f(1, 2) ").unwrap_err();
+        assert!(matches!(*error.error, Error::InvalidArity(_, _, 2, start, next) if start.cursor == 4 && next.cursor == 11));
+    }
+
+    #[test]
+    fn stops_deep_recursions_within_a_small_stack() {
+        let thread = std::thread::Builder::new().stack_size(1 << 20).spawn(|| {
+            let mut evaluator = Evaluator::default();
+            run(&mut evaluator, "fact(n) = n <= 1 ? 1 : n * fact(n - 1)").unwrap();
+            run(&mut evaluator, "h(n) = n <= 0 ? 0 : 1 + (((((h(n - 1))))))").unwrap();
+            assert_eq!(run(&mut evaluator, "fact(5)").unwrap(), "120");
+            assert!(matches!(*run(&mut evaluator, "fact(150)").unwrap_err().error, Error::RecursionLimit(..)));
+            assert!(matches!(*run(&mut evaluator, "h(99)").unwrap_err().error, Error::RecursionLimit(..)));
+            evaluator.set_max_depth(100_000);
+            assert!(matches!(*run(&mut evaluator, "fact(20000)").unwrap_err().error, Error::RecursionLimit(..)));
+            assert_eq!(run(&mut evaluator, "fact(5)").unwrap(), "120");
+        });
+        thread.unwrap().join().unwrap();
+    }
+
+    #[test]
+    fn caps_the_recursion_limit() {
+        let mut evaluator = Evaluator::default();
+        evaluator.set_max_depth(usize::MAX);
+        run(&mut evaluator, "loop(n) = loop(n + 1)").unwrap();
+        let error = run(&mut evaluator, "loop(0)").unwrap_err();
+        assert!(matches!(*error.error, Error::RecursionLimit(_, depth, _, _) if depth < MAX_FRAMES));
+    }
+
+    #[test]
+    fn evaluates_long_chains_of_every_operator() {
+        let chain = |first: &str, operator: &str, operand: &str| format!("{}{}", first, format!(" {} {}", operator, operand).repeat(100));
+        assert_eq!(evaluate(&chain("1", "&&", "1")).unwrap(), "true");
+        assert_eq!(evaluate(&chain("0", "||", "0")).unwrap(), "false");
+        assert_eq!(evaluate(&chain("0", "||", "1")).unwrap(), "true");
+        assert_eq!(evaluate(&chain("true", "==", "true")).unwrap(), "true");
+        assert_eq!(evaluate(&chain("1", "|", "2")).unwrap(), "3");
+        assert_eq!(evaluate(&chain("7", "&", "3")).unwrap(), "3");
+        assert_eq!(evaluate(&chain("1", "<<", "0")).unwrap(), "1");
+        assert_eq!(evaluate(&format!("3 km{}", " to m".repeat(100))).unwrap(), "3000 m");
+        assert_eq!(evaluate(&format!("1{}", "!".repeat(100))).unwrap(), "1");
+        assert_eq!(evaluate(&format!("1e6{}", "%".repeat(3))).unwrap(), "1");
+        assert_eq!(evaluate(&chain("0", "&&", "x")).unwrap(), "false");
+    }
+
+    #[test]
+    fn bounds_integer_powers_and_shifts() {
+        assert!(matches!(*evaluate("1 << 100000000000").unwrap_err().error, Error::OutOfRange(..)));
+        assert_eq!(evaluate("1 >> 100000000000").unwrap(), "0");
+        assert_eq!(evaluate("0 << 100000000000").unwrap(), "0");
+        let mut evaluator = Evaluator::default();
+        evaluator.set_mode(Mode::Integer);
+        assert!(matches!(*run(&mut evaluator, "3 ** 4000000000").unwrap_err().error, Error::OutOfRange(..)));
+        assert!(matches!(*run(&mut evaluator, "1 << 4000000000").unwrap_err().error, Error::OutOfRange(..)));
+        assert_eq!(run(&mut evaluator, "1 ** 4000000000").unwrap(), "1");
+        assert_eq!(run(&mut evaluator, "(-1) ** 4000000001").unwrap(), "-1");
+        assert_eq!(run(&mut evaluator, "1 << 64").unwrap(), "18446744073709551616");
+    }
+
+    #[test]
+    fn raises_only_the_unit_to_its_power() {
+        assert_eq!(evaluate("3 km^2").unwrap(), "3 km^2");
+        assert_eq!(evaluate("100 m^2 + 1 m^2").unwrap(), "101 m^2");
+        assert_eq!(evaluate("5 s^-1").unwrap(), "5 s^-1");
+        assert_eq!(evaluate("2 m^3 to L").unwrap(), "2000 L");
+        assert_eq!(evaluate("3 m^2 50 cm^2").unwrap(), "3.005 m^2");
+        assert_eq!(evaluate("(3 km)^2").unwrap(), "9 km^2");
+        assert_eq!(evaluate("2 pi^2").unwrap(), evaluate("2 * pi^2").unwrap());
+        assert!(matches!(*evaluate("3 foo^2").unwrap_err().error, Error::UnknownUnit(_)));
+    }
+
+    #[test]
+    fn adds_differences_to_temperatures() {
+        assert_eq!(evaluate("20 °C + 10 K").unwrap(), "30 °C");
+        assert_eq!(evaluate("20 °C - 5 K").unwrap(), "15 °C");
+        assert_eq!(evaluate("20 °C + 10 °C").unwrap(), "30 °C");
+        assert_eq!(evaluate("68 °F + 10 K").unwrap(), "86 °F");
+        assert_eq!(evaluate("100 °C to K").unwrap(), "373.15 K");
+    }
+
+    #[test]
+    fn reports_decimal_powers_out_of_range() {
+        let mut evaluator = Evaluator::default();
+        evaluator.set_mode(Mode::Decimal);
+        assert!(matches!(*run(&mut evaluator, "10^(10^30)").unwrap_err().error, Error::OutOfRange(..)));
+        assert!(matches!(*run(&mut evaluator, "2^-(10^30)").unwrap_err().error, Error::OutOfRange(..)));
+        assert!(matches!(*run(&mut evaluator, "0^-1").unwrap_err().error, Error::OutOfDomain(..)));
+        assert!(matches!(*run(&mut evaluator, "(-8)^(1/3)").unwrap_err().error, Error::OutOfDomain(..)));
+        assert_eq!(run(&mut evaluator, "10^100").unwrap(), "1e100");
+    }
+
+    #[test]
+    fn rejects_exponents_too_large_to_read_exactly() {
+        let mut evaluator = Evaluator::default();
+        for mode in [Mode::Rational, Mode::Decimal] {
+            evaluator.set_mode(mode);
+            let error = run(&mut evaluator, "1 + 1e100000000").unwrap_err();
+            assert!(matches!(*error.error, Error::OutOfRange(ref text, position) if text == "1e100000000" && position.cursor == 4));
+            assert!(matches!(*run(&mut evaluator, "-1e-100000000").unwrap_err().error, Error::OutOfRange(..)));
+        }
+        evaluator.set_mode(Mode::Rational);
+        assert_eq!(run(&mut evaluator, "1e3 / 1e-3").unwrap(), "1000000");
+    }
+}
@@ -1,4 +1,5 @@
 use crate::model::*;
+use std::borrow::Cow;
 
 macro_rules! current_method {
     () => {{
@@ -15,29 +16,39 @@ macro_rules! current_method {
 /*                                             INPUT                                              */
 /**************************************************************************************************/
 
-type InputResult = Result<String, Exception>;
+type InputResult<'a> = Result<&'a str, Exception>;
 
-struct InputStream {
-    buffer: String,
+/// Fixed-width integer types that can be written right after a number, as in `255u8`.
+const INTEGER_SUFFIXES: [&str; 10] = ["i8", "u8", "i16", "u16", "i32", "u32", "i64", "u64", "i128", "u128"];
+const IMAGINARY_SUFFIXES: [&str; 2] = ["i", "j"];
+
+/// Reads the source forward, handing out slices of it rather than copies. Lookahead decodes from
+/// the current byte, so that a peek costs as much as the few characters it looks past.
+struct InputStream<'a> {
+    buffer: &'a str,
     pub position: Position
 }
 
-impl From<String> for InputStream {
-    fn from(buffer: String) -> Self {
-        let position = Position { cursor: 0, line: 0, column: 0 };
-        InputStream { buffer, position }
+impl<'a> From<&'a str> for InputStream<'a> {
+    fn from(buffer: &'a str) -> Self {
+        InputStream { buffer, position: Position::default() }
     }
 }
 
-impl InputStream {
+impl<'a> InputStream<'a> {
     pub fn eof(&self, idx: isize) -> bool {
-        self.peek(idx) == None
+        self.peek(idx).is_none()
     }
 
     pub fn peek(&self, idx: isize) -> Option<char> {
-        let pos = self.position.cursor;
-        let pos = if idx < 0 { pos - idx.abs() as usize } else { pos + idx.abs() as usize };
-        self.buffer.chars().nth(pos)
+        let (before, after) = self.buffer.split_at(self.position.byte);
+        if idx < 0 { before.chars().nth_back(idx.unsigned_abs() - 1) }
+        else { after.chars().nth(idx.unsigned_abs()) }
+    }
+
+    /// Gives the source read since the given position.
+    fn get_slice(&self, start: Position) -> &'a str {
+        &self.buffer[start.byte..self.position.byte]
     }
 
     pub fn next(&mut self) -> Result<char, Exception> {
@@ -48,17 +59,13 @@ impl InputStream {
         match self.peek(0) {
             None => Err(Exception::create(Error::UnexpectedEOF(self.position), current_method!())),
             Some(c) => {
-                self.position = Position {
-                    cursor: self.position.cursor + 1,
-                    line: self.position.line + (if '\n' == c { 1 } else { 0 }),
-                    column: if '\n' == c { 0 } else { self.position.column + 1 },
-                };
+                self.position = self.position.advance(c, self.peek(-1));
                 Ok(c)
             }
         }
     }
 
-    pub fn read_escaped<F>(&mut self, mut predicate: F) -> InputResult where F: FnMut(char, &str) -> bool {
+    pub fn read_escaped<F>(&mut self, mut predicate: F) -> InputResult<'a> where F: FnMut(char, &str) -> bool {
         let mut escaped = false;
         self.read_while(|c, str| {
             if escaped {
@@ -73,34 +80,187 @@ impl InputStream {
         })
     }
 
-    pub fn read_identifier(&mut self, with_escaped: bool) -> InputResult {
-        let predicate = |c: char, _: &str| { c.is_alphanumeric() || c == '-' || c == '_' };
+    pub fn read_identifier(&mut self, with_escaped: bool) -> InputResult<'a> {
+        let predicate = |c: char, _: &str| { c.is_alphanumeric() || c == '_' || c == '°' };
         if !with_escaped { self.read_while(predicate) }
         else { self.read_escaped(predicate) }
     }
 
-    pub fn read_number(&mut self) -> InputResult {
-        self.read_while(|c, str| {
+    /// Reads a number, or a date. A time of day such as `10:20` is only read `with_time`, which
+    /// leaves the consequent and the alternative of `c ? 10:20` apart.
+    pub fn read_number(&mut self, with_time: bool) -> InputResult<'a> {
+        if self.is_radix_start() {
+            return match self.read_radix_number() {
+                Err(error) => Err(Exception::relay(error, current_method!())),
+                Ok(value) => Ok(value),
+            };
+        }
+
+        if self.is_date_start(0) || (with_time && self.is_time_start(0)) {
+            return match self.read_date_time() {
+                Err(error) => Err(Exception::relay(error, current_method!())),
+                Ok(value) => Ok(value),
+            };
+        }
+
+        let start = self.position;
+        match self.read_decimal_number() {
+            Err(error) => Err(Exception::relay(error, current_method!())),
+            Ok(_) => {
+                let length = self.get_suffix_length();
+                for _ in 0..length {
+                    self.next().unwrap();
+                }
+                Ok(self.get_slice(start))
+            }
+        }
+    }
+
+    fn read_decimal_number(&mut self) -> InputResult<'a> {
+        let mantissa = self.position;
+        let str = match self.read_while(|c, str| {
             if c == '.' { !str.contains('.') }
-            // else if c == 'e' { !str.contains('e') }
-            // else if c == '+' || c == '-' { str.len() == 0 || str.ends_with('e') }
-            else { c.is_digit(10) }
-        })
+            else { c.is_ascii_digit() }
+        }) {
+            Err(error) => return Err(Exception::relay(error, current_method!())),
+            Ok(value) => value,
+        };
+
+        if !self.is_exponent_start() {
+            return Ok(str);
+        }
+
+        let start = self.position;
+        match self.read_while(|c, exponent| {
+            if exponent.is_empty() { c == 'e' || c == 'E' }
+            else if c == '+' || c == '-' { exponent.len() == 1 }
+            else { c.is_ascii_digit() }
+        }) {
+            Err(error) => Err(Exception::relay(error, current_method!())),
+            Ok(exponent) => {
+                let str = self.get_slice(mantissa);
+                if !exponent.ends_with(|c: char| c.is_ascii_digit()) {
+                    Err(Exception::create(Error::MalformedExponent(str.to_string(), start), current_method!()))
+                } else {
+                    Ok(str)
+                }
+            }
+        }
+    }
+
+    /// Measures the integer suffix, or the imaginary unit of `2i` and `2j`, following a number.
+    fn get_suffix_length(&self) -> usize {
+        let suffix = INTEGER_SUFFIXES.iter().chain(IMAGINARY_SUFFIXES.iter()).find(|suffix| {
+            suffix.chars().enumerate().all(|(idx, c)| self.peek(idx as isize) == Some(c))
+        });
+        match suffix {
+            Some(suffix) if !self.peek(suffix.len() as isize).is_some_and(|c| c.is_alphanumeric() || c == '_') => suffix.len(),
+            _ => 0,
+        }
     }
 
-    pub fn read_while<F>(&mut self, mut predicate: F) -> InputResult where F: FnMut(char, &str) -> bool {
-        let mut str: String = String::from("");
-        while !self.eof(0) {
-            let next = self.peek(0).unwrap();
-            if !predicate(next, &str.clone()) {
+    /// Tells whether an ISO date such as `2026-10-17` starts at the given offset. Such a literal is
+    /// no longer read as two subtractions.
+    fn is_date_start(&self, idx: isize) -> bool {
+        "dddd-dd-dd".chars().enumerate().all(|(offset, pattern)| match self.peek(idx + offset as isize) {
+            Some(c) if pattern == 'd' => c.is_ascii_digit(),
+            Some(c) => c == pattern,
+            None => false,
+        }) && !self.peek(idx + 10).is_some_and(|c| c.is_ascii_digit())
+    }
+
+    /// Tells whether a time of day such as `9:15` or `18:30:05` starts at the given offset. The
+    /// minutes must have two digits, so that `1:2` is never a time.
+    fn is_time_start(&self, idx: isize) -> bool {
+        let hours = (0..2).take_while(|offset| self.peek(idx + offset).is_some_and(|c| c.is_ascii_digit())).count() as isize;
+        hours > 0
+            && self.peek(idx + hours) == Some(':')
+            && (1..3).all(|offset| self.peek(idx + hours + offset).is_some_and(|c| c.is_ascii_digit()))
+            && !self.peek(idx + hours + 3).is_some_and(|c| c.is_ascii_digit())
+    }
+
+    /// Reads a date, a time of day, or both joined by `T` as in `2026-10-17T18:30`.
+    fn read_date_time(&mut self) -> InputResult<'a> {
+        let start = self.position;
+        if self.is_date_start(0) {
+            for _ in 0..10 {
+                self.next().unwrap();
+            }
+            if self.peek(0) != Some('T') || !self.is_time_start(1) {
+                return Ok(self.get_slice(start));
+            }
+            self.next().unwrap();
+        }
+        let time = match self.read_while(|c, time| c.is_ascii_digit() || (c == ':' && time.matches(':').count() < 2)) {
+            Err(error) => return Err(Exception::relay(error, current_method!())),
+            Ok(time) => time,
+        };
+        // Seconds may have a fraction, as in `18:30:05.250`.
+        if time.matches(':').count() == 2 && self.peek(0) == Some('.') && self.peek(1).is_some_and(|c| c.is_ascii_digit()) {
+            if let Err(error) = self.read_while(|c, fraction| c.is_ascii_digit() || (c == '.' && fraction.is_empty())) {
+                return Err(Exception::relay(error, current_method!()));
+            }
+        }
+        Ok(self.get_slice(start))
+    }
+
+    fn is_radix_start(&self) -> bool {
+        self.peek(0) == Some('0') && matches!(self.peek(1), Some('x' | 'X' | 'b' | 'B' | 'o' | 'O'))
+    }
+
+    /// Reads `0x`, `0b` and `0o` literals, whose digits may be grouped with `_`, along with their
+    /// integer suffix if any.
+    fn read_radix_number(&mut self) -> InputResult<'a> {
+        let start = self.position;
+        for _ in 0..2 {
+            if let Err(error) = self.next() {
+                return Err(Exception::relay(error, current_method!()));
+            }
+        }
+        let radix = match self.get_slice(start).to_ascii_lowercase().as_str() {
+            "0x" => 16,
+            "0o" => 8,
+            _ => 2,
+        };
+
+        match self.read_while(|c, _| c.is_alphanumeric() || c == '_') {
+            Err(error) => Err(Exception::relay(error, current_method!())),
+            Ok(digits) => {
+                let str = self.get_slice(start);
+                let digits = INTEGER_SUFFIXES.iter()
+                    .find_map(|suffix| digits.strip_suffix(suffix))
+                    .unwrap_or(digits);
+                if digits.chars().all(|c| c == '_' || c.is_digit(radix)) && digits.chars().any(|c| c != '_') {
+                    Ok(str)
+                } else {
+                    Err(Exception::create(Error::MalformedNumber(str.to_string(), start), current_method!()))
+                }
+            }
+        }
+    }
+
+    fn is_exponent_start(&self) -> bool {
+        match self.peek(0) {
+            Some('e') | Some('E') => match self.peek(1) {
+                None => true,
+                Some(c) => !(c.is_alphabetic() || c == '_'),
+            },
+            _ => false,
+        }
+    }
+
+    /// Reads as long as the predicate accepts the next character, given what it accepted so far.
+    pub fn read_while<F>(&mut self, mut predicate: F) -> InputResult<'a> where F: FnMut(char, &str) -> bool {
+        let start = self.position;
+        while let Some(next) = self.peek(0) {
+            if !predicate(next, self.get_slice(start)) {
                 break;
             }
-            match self.next() {
-                Err(error) => return Err(Exception::relay(error, current_method!())),
-                Ok(c) => str.push(c),
+            if let Err(error) = self.next() {
+                return Err(Exception::relay(error, current_method!()));
             }
         }
-        Ok(str)
+        Ok(self.get_slice(start))
     }
 }
 
@@ -108,31 +268,50 @@ impl InputStream {
 /*                                             LEXER                                              */
 /**************************************************************************************************/
 
-type LexerResult = Result<Token, Exception>;
+type LexerResult<'a> = Result<Token<'a>, Exception>;
+
+/// Reads the tokens of a source one at a time, each borrowing its text from the source. As an
+/// iterator, it stops after the first error.
+pub struct Lexer<'a> {
+    input: InputStream<'a>,
+    failed: bool,
+    /// Number of `?` still waiting for the `:` of their conditional.
+    conditions: usize,
+}
 
-pub struct Lexer {
-    input: InputStream,
+impl<'a> From<&'a str> for Lexer<'a> {
+    fn from(content: &'a str) -> Self {
+        Lexer { input: InputStream::from(content), failed: false, conditions: 0 }
+    }
 }
 
-impl From<String> for Lexer {
-    fn from(content: String) -> Self {
-        Lexer { input: InputStream::from(content) }
+impl<'a> Iterator for Lexer<'a> {
+    type Item = LexerResult<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed || self.input.eof(0) {
+            return None;
+        }
+        let token = self.read_token();
+        self.failed = token.is_err();
+        Some(token)
     }
 }
 
-impl Lexer {
-    fn is_operator(c: char) -> bool { None != "+-*/%=&|!><^~".find(c) }
-    fn is_punctuation(c: char) -> bool { None != ",;(){}[]:.#".find(c) }
-    fn is_whitespace(c: char) -> bool { None != "\t\r\n ".find(c) }
+impl<'a> Lexer<'a> {
+    fn is_operator(c: char) -> bool { "+-*/%=&|!><^~?\\".find(c).is_some() }
+    fn is_punctuation(c: char) -> bool { ",;(){}[]:.#".find(c).is_some() }
+    fn is_whitespace(c: char) -> bool { "\t\r\n ".find(c).is_some() }
+    fn is_currency(c: char) -> bool { "$€£¥₹₩₽₺₪₱₫฿".find(c).is_some() }
 
     pub fn eof(&self) -> bool {
         self.input.eof(0)
     }
 
-    pub fn lex(&mut self) -> Result<Vec<Token>, Exception> {
+    pub fn lex(&mut self) -> Result<Vec<Token<'a>>, Exception> {
         let mut buffer: Vec<Token> = vec![];
-        while !self.input.eof(0) {
-            match self.next() {
+        for token in self.by_ref() {
+            match token {
                 Err(error) => return Err(Exception::relay(error, current_method!())),
                 Ok(token) => buffer.push(token),
             }
@@ -140,10 +319,10 @@ impl Lexer {
         Ok(buffer)
     }
 
-    pub fn read(&mut self) -> Vec<Token> {
+    pub fn read(&mut self) -> Vec<Token<'a>> {
         let mut buffer: Vec<Token> = vec![];
-        while !self.input.eof(0) {
-            match self.next() {
+        for token in self.by_ref() {
+            match token {
                 Err(error) => panic!("{}", error),
                 Ok(token) => buffer.push(token),
             }
@@ -151,7 +330,7 @@ impl Lexer {
         buffer
     }
 
-    pub fn next(&mut self) -> LexerResult {
+    pub fn read_token(&mut self) -> LexerResult<'a> {
         if self.input.eof(0) {
             return Err(Exception::create(Error::UnexpectedEOF(self.input.position), current_method!()));
         }
@@ -159,73 +338,204 @@ impl Lexer {
         let c = self.input.peek(0).unwrap();
         if Lexer::is_whitespace(c) { self.read_whitespace() }
         else if self.is_number_start() { self.read_number() }
+        else if self.is_element_operator_start() { self.read_operator() }
         else if Lexer::is_punctuation(c) { self.read_punctuation() }
         else if self.is_ident_start(c) { self.read_identifier() }
         else if Lexer::is_operator(c) { self.read_operator() }
+        else if Lexer::is_currency(c) { self.read_currency() }
         else { Err(Exception::create(Error::UnprocessableCharacter(c, self.input.position), current_method!())) }
     }
 
+    /// Besides letters, identifiers may contain `°` for the units `°C` and `°F`.
     fn is_ident_start(&self, c: char) -> bool {
-        c.is_alphanumeric() || c == '_'
+        c.is_alphanumeric() || c == '_' || c == '°'
     }
 
     fn is_number_start(&self) -> bool {
         if self.input.eof(0) { return false; }
         let current = self.input.peek(0).unwrap();
-        if current.is_digit(10) { return true; } // 1
+        if current.is_ascii_digit() { return true; } // 1
 
         if self.input.eof(1) { return false; }
         let next = self.input.peek(1).unwrap();
-        current == '.' && next.is_digit(10) // .1
+        current == '.' && next.is_ascii_digit() // .1
+    }
+
+    /// The element-wise operators `.*` and `./` start with the punctuation `.`.
+    fn is_element_operator_start(&self) -> bool {
+        self.input.peek(0) == Some('.') && matches!(self.input.peek(1), Some('*' | '/'))
+    }
+
+    /// Identifiers don't take escapes, `A\b` being the left division of `A` by `b`.
+    fn read_identifier(&mut self) -> LexerResult<'a> {
+        let start = self.input.position;
+        match self.input.read_identifier(false) {
+            Err(error) => Err(Exception::relay(error, current_method!())),
+            Ok(value) => Ok(Token::Identifier(Cow::Borrowed(value), start, self.input.position, Trivia::default())),
+        }
     }
 
-    fn read_identifier(&mut self) -> LexerResult {
+    /// Reads a currency symbol alone, so that it can stand before its amount as in `$45`.
+    fn read_currency(&mut self) -> LexerResult<'a> {
         let start = self.input.position;
-        match self.input.read_identifier(true) {
+        match self.input.next() {
             Err(error) => Err(Exception::relay(error, current_method!())),
-            Ok(value) => Ok(Token::Identifier(value, start, self.input.position)),
+            Ok(_) => Ok(Token::Identifier(Cow::Borrowed(self.input.get_slice(start)), start, self.input.position, Trivia::default())),
         }
     }
 
-    fn read_number(&mut self) -> LexerResult {
+    fn read_number(&mut self) -> LexerResult<'a> {
         let start = self.input.position;
-        match self.input.read_number() {
+        match self.input.read_number(self.conditions == 0) {
             Err(error) => Err(Exception::relay(error, current_method!())),
-            Ok(value) => Ok(Token::Number(value, start, self.input.position)),
+            Ok(value) => Ok(Token::Number(Cow::Borrowed(value), start, self.input.position, Trivia::default())),
         }
     }
 
-    fn read_operator(&mut self) -> LexerResult {
+    fn read_operator(&mut self) -> LexerResult<'a> {
         let start = self.input.position;
         match self.input.next() {
             Err(error) => Err(Exception::relay(error, current_method!())),
             Ok(value) => {
-                if "&|=".contains(value) {
-                    if let Some(next) = self.input.peek(0) {
-                        if next == value {
-                            let value = format!("{}{}", value, self.input.next().unwrap());
-                            return Ok(Token::Operator(value,start,self.input.position));
-                        }
+                if let Some(next) = self.input.peek(0) {
+                    if ("&|=*<>".contains(value) && next == value)
+                        || ("<>!".contains(value) && next == '=')
+                        || (value == '.' && "*/".contains(next)) {
+                        self.input.next().unwrap();
                     }
                 }
-                Ok(Token::Operator(String::from(value), start, self.input.position))
+                let value = self.input.get_slice(start);
+                if value == "?" { self.conditions += 1; }
+                Ok(Token::Operator(Cow::Borrowed(value), start, self.input.position, Trivia::default()))
             },
         }
     }
 
-    fn read_punctuation(&mut self) -> LexerResult {
+    fn read_punctuation(&mut self) -> LexerResult<'a> {
         let start = self.input.position;
         match self.input.next() {
             Err(error) => Err(Exception::relay(error, current_method!())),
-            Ok(value) => Ok(Token::Punctuation(value, start, self.input.position)),
+            Ok(value) => {
+                if value == ':' { self.conditions = self.conditions.saturating_sub(1); }
+                Ok(Token::Punctuation(value, start, self.input.position, Trivia::default()))
+            },
         }
     }
 
-    fn read_whitespace(&mut self) -> LexerResult {
+    fn read_whitespace(&mut self) -> LexerResult<'a> {
         let start = self.input.position;
         match self.input.read_while(|c, _| { Lexer::is_whitespace(c) }) {
             Err(error) => Err(Exception::relay(error, current_method!())),
-            Ok(value) => Ok(Token::Whitespace(value, start, self.input.position)),
+            Ok(value) => Ok(Token::Whitespace(Cow::Borrowed(value), start, self.input.position, Trivia::default())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Gives the values of the tokens other than whitespace.
+    fn lex(text: &str) -> Vec<String> {
+        Lexer::from(text).read().iter().filter(|token| !token.is_whitespace(None)).map(|token| token.get_value()).collect()
+    }
+
+    #[test]
+    fn reads_times_outside_conditionals() {
+        assert_eq!(lex("18:30 - 09:15"), ["18:30", "-", "09:15"]);
+        assert_eq!(lex("x ? 10:20"), ["x", "?", "10", ":", "20"]);
+        assert_eq!(lex("x ? 1 : 2 + 10:20"), ["x", "?", "1", ":", "2", "+", "10:20"]);
+        assert_eq!(lex("x ? 2026-10-17T18:30 : 2"), ["x", "?", "2026-10-17T18:30", ":", "2"]);
+    }
+
+    #[test]
+    fn reads_scientific_notation() {
+        assert_eq!(lex("1.5e3 + 2E-2"), ["1.5e3", "+", "2E-2"]);
+        assert_eq!(lex("3e+4"), ["3e+4"]);
+        assert_eq!(lex(".5e1"), [".5e1"]);
+        let error = Lexer::from("1e").lex().unwrap_err();
+        assert!(matches!(*error.error, Error::MalformedExponent(ref text, _) if text == "1e"));
+        assert!(Lexer::from("1e+").lex().is_err());
+    }
+
+    #[test]
+    fn reads_double_stars_as_one_operator() {
+        assert_eq!(lex("2**3^2"), ["2", "**", "3", "^", "2"]);
+    }
+
+    #[test]
+    fn reads_comparison_and_logical_operators() {
+        assert_eq!(lex("a<=b>=c!=d==e"), ["a", "<=", "b", ">=", "c", "!=", "d", "==", "e"]);
+        assert_eq!(lex("a&&!b||c"), ["a", "&&", "!", "b", "||", "c"]);
+        assert_eq!(lex("a<b>c"), ["a", "<", "b", ">", "c"]);
+    }
+
+    #[test]
+    fn reads_radix_literals() {
+        assert_eq!(lex("0xFF & 0b1010 | 0o17"), ["0xFF", "&", "0b1010", "|", "0o17"]);
+        assert_eq!(lex("1 << 4 >> 2"), ["1", "<<", "4", ">>", "2"]);
+    }
+
+    #[test]
+    fn reads_imaginary_suffixes() {
+        assert_eq!(lex("3+4i"), ["3", "+", "4i"]);
+        assert_eq!(lex("2.5j"), ["2.5j"]);
+        assert_eq!(lex("2in"), ["2", "in"]);
+        assert_eq!(lex("255u8"), ["255u8"]);
+    }
+
+    #[test]
+    fn reads_currency_symbols_alone() {
+        assert_eq!(lex("$45+5€"), ["$", "45", "+", "5", "€"]);
+        assert_eq!(lex("30 EUR to £"), ["30", "EUR", "to", "£"]);
+    }
+
+    #[test]
+    fn reads_dates_as_single_tokens() {
+        assert_eq!(lex("2026-10-17 - 2026-01-01"), ["2026-10-17", "-", "2026-01-01"]);
+        assert_eq!(lex("2026-10-17T18:30:05.5"), ["2026-10-17T18:30:05.5"]);
+        assert_eq!(lex("2026-10-170"), ["2026", "-", "10", "-", "170"]);
+        assert_eq!(lex("a-b"), ["a", "-", "b"]);
+    }
+
+    #[test]
+    fn keeps_whitespace_as_tokens() {
+        let tokens = Lexer::from(" 1\t+\r\n2 ").read();
+        let types: Vec<String> = tokens.iter().map(|token| token.get_type()).collect();
+        assert_eq!(types, ["Whitespace", "Number", "Whitespace", "Operator", "Whitespace", "Number", "Whitespace"]);
+        assert_eq!(tokens[4].get_value(), "\r\n");
+        assert_eq!(tokens.iter().map(|token| token.get_text()).collect::<String>(), " 1\t+\r\n2 ");
+    }
+
+    #[test]
+    fn positions_tokens_after_wide_characters() {
+        let tokens = Lexer::from("é𝑥 + 1").lex().unwrap();
+        let one = tokens.iter().find(|token| token.get_value() == "1").unwrap();
+        let start = one.get_start();
+        assert_eq!((start.cursor, start.byte, start.utf16, start.column), (5, 9, 6, 5));
+        assert_eq!(one.get_next().utf16, 7);
+    }
+
+    #[test]
+    fn stops_streaming_after_an_error() {
+        let mut lexer = Lexer::from("1 \" 2");
+        assert_eq!(lexer.next().unwrap().unwrap().get_value(), "1");
+        assert!(lexer.next().unwrap().unwrap().is_whitespace(None));
+        assert!(matches!(*lexer.next().unwrap().unwrap_err().error, Error::UnprocessableCharacter('"', _)));
+        assert!(lexer.next().is_none());
+    }
+
+    #[test]
+    fn streams_large_inputs() {
+        let text = "12.5 * x + ".repeat(100_000) + "1";
+        let mut count = 0;
+        for token in Lexer::from(text.as_str()) {
+            let token = token.unwrap();
+            assert!(matches!(token.get_value().as_str(), "12.5" | "*" | "x" | "+" | "1" | " "));
+            count += 1;
         }
+        assert_eq!(count, 800_001);
+        let last = Lexer::from(text.as_str()).last().unwrap().unwrap();
+        assert_eq!(last.get_start().cursor, text.len() - 1);
     }
 }
@@ -1,34 +1,185 @@
-mod model;
-mod lexer;
-mod parser;
+pub mod model;
+pub mod lexer;
+pub mod parser;
+pub mod value;
+pub mod decimal;
+pub mod matrix;
+pub mod unit;
+pub mod currency;
+pub mod calendar;
+pub mod library;
+pub mod environment;
+pub mod evaluator;
 
 use wasm_bindgen::prelude::*;
 use model::*;
 use lexer::*;
 use parser::*;
+use evaluator::*;
+use value::*;
+use decimal::*;
+use currency::*;
 
-#[cfg(feature = "wee_alloc")]
-#[global_allocator]
-static ALLOC: wee_alloc::WeeAlloc = wee_alloc::WeeAlloc::INIT;
+/// Positions have their offset in `char`s, unless `offsets` lists others among `char`, `byte` and
+/// `utf16`, as `"utf16"` to highlight the source in JavaScript.
+fn get_offsets(with_position: bool, offsets: Option<String>) -> Result<Option<Offsets>, JsError> {
+  match (with_position, offsets) {
+    (false, _) => Ok(None),
+    (true, None) => Ok(Some(Offsets::default())),
+    (true, Some(offsets)) => match Offsets::parse(&offsets) {
+      None => Err(JsError::new(format!(r#"Unknown offsets "{}""#, offsets).as_str())),
+      Some(offsets) => Ok(Some(offsets)),
+    },
+  }
+}
 
 #[wasm_bindgen]
-pub fn lex(text: &str, with_position: bool) -> Result<String, JsError> {
-  match Lexer::from(String::from(text)).lex() {
+pub fn lex(text: &str, with_position: bool, offsets: Option<String>) -> Result<String, JsError> {
+  let offsets = get_offsets(with_position, offsets)?;
+  match Lexer::from(text).lex() {
     Err(error) => Err(JsError::new(format!(r#"{}"#, error).as_str())),
     Ok(tokens) => {
-      let mut str = String::from("");
-      for token in tokens {
-        str = format!("{},{}", str, token.get_json(with_position))
-      }
-      Ok(format!(r#"[{}]"#, str.trim_start_matches(&[','])))
+      let json: Vec<String> = tokens.iter().map(|token| token.get_json(offsets)).collect();
+      Ok(format!(r#"[{}]"#, json.join(",")))
     }
   }
 }
 
 #[wasm_bindgen]
-pub fn parse(text: &str, with_position: bool) -> Result<String, JsError> {
-  match Parser::from(String::from(text)).parse() {
+pub fn parse(text: &str, with_position: bool, offsets: Option<String>) -> Result<String, JsError> {
+  let offsets = get_offsets(with_position, offsets)?;
+  let tokens = match Lexer::from(text).lex() {
+    Err(error) => return Err(JsError::new(format!(r#"{}"#, error).as_str())),
+    Ok(tokens) => tokens,
+  };
+  match Parser::from(tokens).parse() {
     Err(error) => Err(JsError::new(format!(r#"{}"#, error).as_str())),
-    Ok(node) => Ok(format!(r#"{}"#, node.get_json(with_position)))
+    Ok(node) => Ok(node.get_json(offsets).to_string())
+  }
+}
+
+#[wasm_bindgen]
+pub fn evaluate(text: &str) -> Result<String, JsError> {
+  run(&mut Evaluator::default(), text, Format::default())
+}
+
+/// Keeps the variables assigned by one evaluation available to the next ones.
+#[wasm_bindgen]
+#[derive(Default)]
+pub struct Session {
+  evaluator: Evaluator,
+  format: Format,
+}
+
+#[wasm_bindgen]
+impl Session {
+  #[wasm_bindgen(constructor)]
+  pub fn new() -> Session {
+    Session::default()
+  }
+
+  pub fn evaluate(&mut self, text: &str) -> Result<String, JsError> {
+    run(&mut self.evaluator, text, self.format)
+  }
+
+  /// Switches between the `float`, `integer` (programmer), `rational` (exact) and `decimal`
+  /// (arbitrary-precision) evaluation modes, or evaluates with a fixed-width integer type such as
+  /// `u8` or `i64`.
+  #[wasm_bindgen(js_name = setMode)]
+  pub fn set_mode(&mut self, mode: &str) -> Result<(), JsError> {
+    match (mode, IntegerType::parse(mode)) {
+      ("float", _) => self.evaluator.set_mode(Mode::Float),
+      ("integer", _) => self.evaluator.set_mode(Mode::Integer),
+      ("rational", _) => self.evaluator.set_mode(Mode::Rational),
+      ("decimal", _) => self.evaluator.set_mode(Mode::Decimal),
+      (_, Some(integer_type)) => self.evaluator.set_mode(Mode::Fixed(integer_type)),
+      _ => return Err(JsError::new(format!(r#"Unknown mode "{}""#, mode).as_str())),
+    }
+    Ok(())
+  }
+
+  /// Chooses between `wrapping`, `saturating` and `error` when fixed-width arithmetic overflows.
+  #[wasm_bindgen(js_name = setOverflow)]
+  pub fn set_overflow(&mut self, overflow: &str) -> Result<(), JsError> {
+    match overflow {
+      "wrapping" => self.evaluator.set_overflow(Overflow::Wrapping),
+      "saturating" => self.evaluator.set_overflow(Overflow::Saturating),
+      "error" => self.evaluator.set_overflow(Overflow::Error),
+      _ => return Err(JsError::new(format!(r#"Unknown overflow policy "{}""#, overflow).as_str())),
+    }
+    Ok(())
+  }
+
+  /// Sets the number of significant digits of the decimal mode.
+  #[wasm_bindgen(js_name = setPrecision)]
+  pub fn set_precision(&mut self, precision: u32) -> Result<(), JsError> {
+    if precision == 0 {
+      return Err(JsError::new("Precision must be at least one digit"));
+    }
+    self.evaluator.set_precision(precision as u64);
+    Ok(())
+  }
+
+  /// Chooses the rounding of the decimal mode: `half-even`, `half-up`, `down`, `up`, `ceiling` or
+  /// `floor`.
+  #[wasm_bindgen(js_name = setRounding)]
+  pub fn set_rounding(&mut self, rounding: &str) -> Result<(), JsError> {
+    match Rounding::parse(rounding) {
+      None => Err(JsError::new(format!(r#"Unknown rounding "{}""#, rounding).as_str())),
+      Some(rounding) => {
+        self.evaluator.set_rounding(rounding);
+        Ok(())
+      },
+    }
+  }
+
+  /// Writes complex results in `rectangular` (`3+4i`) or `polar` (`5∠0.927`) form.
+  #[wasm_bindgen(js_name = setFormat)]
+  pub fn set_format(&mut self, format: &str) -> Result<(), JsError> {
+    match format {
+      "rectangular" => self.format = Format::Rectangular,
+      "polar" => self.format = Format::Polar,
+      _ => return Err(JsError::new(format!(r#"Unknown format "{}""#, format).as_str())),
+    }
+    Ok(())
+  }
+
+  /// Loads the exchange rates used to convert between currencies, as JSON
+  /// (`{"base": "EUR", "timestamp": "2024-05-01", "rates": {"USD": 1.0823}}`) or as CSV lines of
+  /// `code,rate,timestamp`.
+  #[wasm_bindgen(js_name = setRates)]
+  pub fn set_rates(&mut self, rates: &str) -> Result<(), JsError> {
+    match Rates::parse(rates) {
+      Err(error) => Err(JsError::new(error.as_str())),
+      Ok(rates) => {
+        self.evaluator.set_rates(rates);
+        Ok(())
+      },
+    }
+  }
+
+  #[wasm_bindgen(js_name = setMaxDepth)]
+  pub fn set_max_depth(&mut self, max_depth: usize) {
+    self.evaluator.set_max_depth(max_depth)
+  }
+
+  pub fn clear(&mut self) {
+    self.evaluator.get_environment().clear()
+  }
+}
+
+fn run(evaluator: &mut Evaluator, text: &str, format: Format) -> Result<String, JsError> {
+  let tokens = match Lexer::from(text).lex() {
+    Err(error) => return Err(JsError::new(format!(r#"{}"#, error).as_str())),
+    Ok(tokens) => tokens,
+  };
+  let mut parser = Parser::from(tokens);
+  parser.set_xor_caret(matches!(evaluator.get_mode(), Mode::Integer | Mode::Fixed(_)));
+  match parser.parse() {
+    Err(error) => Err(JsError::new(format!(r#"{}"#, error).as_str())),
+    Ok(node) => match evaluator.evaluate(&node) {
+      Err(error) => Err(JsError::new(format!(r#"{}"#, error).as_str())),
+      Ok(value) => Ok(value.get_json(format)),
+    }
   }
 }
@@ -0,0 +1,331 @@
+use std::f64::consts;
+use chrono::{Datelike, Timelike};
+use num_complex::Complex64;
+use num_traits::Signed;
+use std::fmt::{Display, Formatter};
+use crate::calendar::*;
+use crate::decimal::*;
+use crate::matrix::*;
+use crate::value::*;
+
+/**************************************************************************************************/
+/*                                           CONSTANTS                                            */
+/**************************************************************************************************/
+
+static CONSTANTS: [(&str, Value); 14] = [
+    ("pi", Value::Number(consts::PI)),
+    ("π", Value::Number(consts::PI)),
+    ("e", Value::Number(consts::E)),
+    ("tau", Value::Number(consts::TAU)),
+    ("τ", Value::Number(consts::TAU)),
+    ("phi", Value::Number(1.618_033_988_749_895)),
+    ("φ", Value::Number(1.618_033_988_749_895)),
+    ("inf", Value::Number(f64::INFINITY)),
+    ("infinity", Value::Number(f64::INFINITY)),
+    ("nan", Value::Number(f64::NAN)),
+    ("i", Value::Complex(Complex64::new(0.0, 1.0))),
+    ("j", Value::Complex(Complex64::new(0.0, 1.0))),
+    ("true", Value::Boolean(true)),
+    ("false", Value::Boolean(false)),
+];
+
+pub fn get_constant(name: &str) -> Option<Value> {
+    CONSTANTS.iter().find(|(key, _)| *key == name).map(|(_, value)| value.clone())
+}
+
+/**************************************************************************************************/
+/*                                           FUNCTIONS                                            */
+/**************************************************************************************************/
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Arity {
+    Exact(usize),
+    Range(usize, usize),
+    AtLeast(usize),
+}
+
+impl Arity {
+    pub fn accepts(&self, count: usize) -> bool {
+        match self {
+            Arity::Exact(expected) => count == *expected,
+            Arity::Range(min, max) => *min <= count && count <= *max,
+            Arity::AtLeast(min) => *min <= count,
+        }
+    }
+}
+
+impl Display for Arity {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Arity::Exact(expected) => write!(f, "{}", expected),
+            Arity::Range(min, max) => write!(f, "{} to {}", min, max),
+            Arity::AtLeast(min) => write!(f, "at least {}", min),
+        }
+    }
+}
+
+/// A built-in function returns `None` when its arguments are outside of its domain.
+pub struct Function {
+    pub name: &'static str,
+    pub arity: Arity,
+    pub call: fn(&[f64]) -> Option<f64>,
+}
+
+fn defined(value: f64, domain: bool) -> Option<f64> {
+    if domain { Some(value) } else { None }
+}
+
+static FUNCTIONS: [Function; 29] = [
+    Function { name: "sqrt", arity: Arity::Exact(1), call: |args| defined(args[0].sqrt(), 0.0 <= args[0]) },
+    Function { name: "cbrt", arity: Arity::Exact(1), call: |args| Some(args[0].cbrt()) },
+    Function { name: "abs", arity: Arity::Exact(1), call: |args| Some(args[0].abs()) },
+    Function { name: "sign", arity: Arity::Exact(1), call: |args| {
+        Some(if args[0] == 0.0 || args[0].is_nan() { args[0] } else { args[0].signum() })
+    } },
+    Function { name: "floor", arity: Arity::Exact(1), call: |args| Some(args[0].floor()) },
+    Function { name: "ceil", arity: Arity::Exact(1), call: |args| Some(args[0].ceil()) },
+    Function { name: "round", arity: Arity::Range(1, 2), call: |args| {
+        let factor = 10f64.powf(args.get(1).copied().unwrap_or(0.0).trunc());
+        Some((args[0] * factor).round() / factor)
+    } },
+    Function { name: "trunc", arity: Arity::Exact(1), call: |args| Some(args[0].trunc()) },
+    Function { name: "exp", arity: Arity::Exact(1), call: |args| Some(args[0].exp()) },
+    Function { name: "ln", arity: Arity::Exact(1), call: |args| defined(args[0].ln(), 0.0 < args[0]) },
+    Function { name: "log", arity: Arity::Range(1, 2), call: |args| match args.get(1) {
+        None => defined(args[0].log10(), 0.0 < args[0]),
+        Some(base) => defined(args[0].log(*base), 0.0 < args[0] && 0.0 < *base && *base != 1.0),
+    } },
+    Function { name: "log2", arity: Arity::Exact(1), call: |args| defined(args[0].log2(), 0.0 < args[0]) },
+    Function { name: "log10", arity: Arity::Exact(1), call: |args| defined(args[0].log10(), 0.0 < args[0]) },
+    Function { name: "sin", arity: Arity::Exact(1), call: |args| Some(args[0].sin()) },
+    Function { name: "cos", arity: Arity::Exact(1), call: |args| Some(args[0].cos()) },
+    Function { name: "tan", arity: Arity::Exact(1), call: |args| Some(args[0].tan()) },
+    Function { name: "asin", arity: Arity::Exact(1), call: |args| defined(args[0].asin(), args[0].abs() <= 1.0) },
+    Function { name: "acos", arity: Arity::Exact(1), call: |args| defined(args[0].acos(), args[0].abs() <= 1.0) },
+    Function { name: "atan", arity: Arity::Exact(1), call: |args| Some(args[0].atan()) },
+    Function { name: "sinh", arity: Arity::Exact(1), call: |args| Some(args[0].sinh()) },
+    Function { name: "cosh", arity: Arity::Exact(1), call: |args| Some(args[0].cosh()) },
+    Function { name: "tanh", arity: Arity::Exact(1), call: |args| Some(args[0].tanh()) },
+    Function { name: "asinh", arity: Arity::Exact(1), call: |args| Some(args[0].asinh()) },
+    Function { name: "acosh", arity: Arity::Exact(1), call: |args| defined(args[0].acosh(), 1.0 <= args[0]) },
+    Function { name: "atanh", arity: Arity::Exact(1), call: |args| defined(args[0].atanh(), args[0].abs() < 1.0) },
+    Function { name: "min", arity: Arity::AtLeast(1), call: |args| Some(args.iter().copied().fold(f64::INFINITY, f64::min)) },
+    Function { name: "max", arity: Arity::AtLeast(1), call: |args| Some(args.iter().copied().fold(f64::NEG_INFINITY, f64::max)) },
+    Function { name: "hypot", arity: Arity::AtLeast(1), call: |args| Some(args.iter().fold(0.0, |a, b| a.hypot(*b))) },
+    Function { name: "atan2", arity: Arity::Exact(2), call: |args| Some(args[0].atan2(args[1])) },
+];
+
+pub fn get_function(name: &str) -> Option<&'static Function> {
+    FUNCTIONS.iter().find(|function| function.name == name)
+}
+
+/**************************************************************************************************/
+/*                                       COMPLEX FUNCTIONS                                        */
+/**************************************************************************************************/
+
+/// The functions accepting complex arguments, also used when a real function is out of its domain
+/// as for `sqrt(-1)`.
+pub struct ComplexFunction {
+    pub name: &'static str,
+    pub call: fn(Complex64) -> Option<Complex64>,
+}
+
+static COMPLEX_FUNCTIONS: [ComplexFunction; 11] = [
+    ComplexFunction { name: "sqrt", call: |arg| Some(arg.sqrt()) },
+    ComplexFunction { name: "exp", call: |arg| Some(arg.exp()) },
+    ComplexFunction { name: "ln", call: |arg| if arg == Complex64::new(0.0, 0.0) { None } else { Some(arg.ln()) } },
+    ComplexFunction { name: "abs", call: |arg| Some(Complex64::new(arg.norm(), 0.0)) },
+    ComplexFunction { name: "arg", call: |arg| Some(Complex64::new(arg.arg(), 0.0)) },
+    ComplexFunction { name: "conj", call: |arg| Some(arg.conj()) },
+    ComplexFunction { name: "re", call: |arg| Some(Complex64::new(arg.re, 0.0)) },
+    ComplexFunction { name: "im", call: |arg| Some(Complex64::new(arg.im, 0.0)) },
+    ComplexFunction { name: "sin", call: |arg| Some(arg.sin()) },
+    ComplexFunction { name: "cos", call: |arg| Some(arg.cos()) },
+    ComplexFunction { name: "tan", call: |arg| Some(arg.tan()) },
+];
+
+pub fn get_complex_function(name: &str) -> Option<&'static ComplexFunction> {
+    COMPLEX_FUNCTIONS.iter().find(|function| function.name == name)
+}
+
+/**************************************************************************************************/
+/*                                       DECIMAL FUNCTIONS                                        */
+/**************************************************************************************************/
+
+/// The functions computed to the precision of the context in the decimal mode, the others being
+/// computed with floats.
+pub struct DecimalFunction {
+    pub name: &'static str,
+    pub call: fn(&Decimal, &Context) -> Option<Decimal>,
+}
+
+static DECIMAL_FUNCTIONS: [DecimalFunction; 7] = [
+    DecimalFunction { name: "sqrt", call: Decimal::sqrt },
+    DecimalFunction { name: "abs", call: |arg, _| Some(Decimal { digits: arg.digits.abs(), exponent: arg.exponent }) },
+    DecimalFunction { name: "exp", call: Decimal::exp },
+    DecimalFunction { name: "ln", call: Decimal::ln },
+    DecimalFunction { name: "sin", call: Decimal::sin },
+    DecimalFunction { name: "cos", call: Decimal::cos },
+    DecimalFunction { name: "tan", call: Decimal::tan },
+];
+
+pub fn get_decimal_function(name: &str) -> Option<&'static DecimalFunction> {
+    DECIMAL_FUNCTIONS.iter().find(|function| function.name == name)
+}
+
+pub fn get_decimal_constant(name: &str, context: &Context) -> Option<Decimal> {
+    match name {
+        "pi" | "π" => Some(Decimal::pi(context)),
+        "tau" | "τ" => Some(Decimal::tau(context)),
+        "e" => Some(Decimal::e(context)),
+        _ => None,
+    }
+}
+
+/**************************************************************************************************/
+/*                                         DATE FUNCTIONS                                         */
+/**************************************************************************************************/
+
+/// The functions reading a field of a date or a time, which give `None` for other values.
+pub struct DateFunction {
+    pub name: &'static str,
+    pub call: fn(&Value) -> Option<Value>,
+}
+
+static DATE_FUNCTIONS: [DateFunction; 13] = [
+    DateFunction { name: "year", call: |arg| get_date(arg).map(|date| Value::Number(date.year() as f64)) },
+    DateFunction { name: "month", call: |arg| get_date(arg).map(|date| Value::Number(date.month() as f64)) },
+    DateFunction { name: "day", call: |arg| get_date(arg).map(|date| Value::Number(date.day() as f64)) },
+    DateFunction { name: "dayofyear", call: |arg| get_date(arg).map(|date| Value::Number(date.ordinal() as f64)) },
+    // Days are numbered from Monday and weeks as in ISO 8601 by `week` and `isoweek` alike,
+    // `2027-01-01` being in the week 53 of 2026.
+    DateFunction { name: "weekday", call: |arg| get_date(arg).map(|date| Value::Number(date.weekday().number_from_monday() as f64)) },
+    DateFunction { name: "week", call: |arg| get_date(arg).map(|date| Value::Number(date.iso_week().week() as f64)) },
+    DateFunction { name: "isoweek", call: |arg| get_date(arg).map(|date| Value::Number(date.iso_week().week() as f64)) },
+    DateFunction { name: "isoyear", call: |arg| get_date(arg).map(|date| Value::Number(date.iso_week().year() as f64)) },
+    DateFunction { name: "hour", call: |arg| get_time(arg).map(|time| Value::Number(time.hour() as f64)) },
+    DateFunction { name: "minute", call: |arg| get_time(arg).map(|time| Value::Number(time.minute() as f64)) },
+    DateFunction { name: "second", call: |arg| get_time(arg).map(|time| Value::Number(time.second() as f64 + time.nanosecond() as f64 / 1e9)) },
+    DateFunction { name: "date", call: |arg| get_date(arg).map(Value::Date) },
+    DateFunction { name: "time", call: |arg| get_time(arg).map(Value::Time) },
+];
+
+pub fn get_date_function(name: &str) -> Option<&'static DateFunction> {
+    DATE_FUNCTIONS.iter().find(|function| function.name == name)
+}
+
+/**************************************************************************************************/
+/*                                        MATRIX FUNCTIONS                                        */
+/**************************************************************************************************/
+
+/// Why a matrix function has no value for its arguments.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Failure {
+    /// The argument at this index has the wrong type or shape.
+    Argument(usize),
+    /// The arguments at these indices have shapes that don't agree.
+    Shape(usize, usize),
+    /// The matrix has no inverse.
+    Singular,
+}
+
+/// The functions of vectors and matrices.
+pub struct MatrixFunction {
+    pub name: &'static str,
+    pub arity: usize,
+    pub call: fn(&[Value]) -> Result<Value, Failure>,
+}
+
+/// Size of the largest identity matrix built, so that a typo can't exhaust the memory.
+const MAX_IDENTITY: f64 = 1000.0;
+
+static MATRIX_FUNCTIONS: [MatrixFunction; 6] = [
+    MatrixFunction { name: "transpose", arity: 1, call: |args| match &args[0] {
+        Value::Matrix(matrix) => Ok(Value::Matrix(matrix.transpose())),
+        Value::Vector(vector) => Ok(Value::Matrix(Matrix::from_row(vector))),
+        _ => Err(Failure::Argument(0)),
+    }},
+    MatrixFunction { name: "det", arity: 1, call: |args| match &args[0] {
+        Value::Matrix(matrix) => matrix.determinant().map(Value::Number).ok_or(Failure::Argument(0)),
+        _ => Err(Failure::Argument(0)),
+    }},
+    MatrixFunction { name: "inv", arity: 1, call: |args| match &args[0] {
+        Value::Matrix(matrix) if matrix.is_square() => matrix.inverse().map(Value::Matrix).ok_or(Failure::Singular),
+        _ => Err(Failure::Argument(0)),
+    }},
+    MatrixFunction { name: "identity", arity: 1, call: |args| match &args[0] {
+        Value::Number(size) if size.fract() == 0.0 && (0.0..=MAX_IDENTITY).contains(size) => {
+            Ok(Value::Matrix(Matrix::identity(*size as usize)))
+        },
+        _ => Err(Failure::Argument(0)),
+    }},
+    MatrixFunction { name: "dot", arity: 2, call: |args| match (&args[0], &args[1]) {
+        (Value::Vector(u), Value::Vector(v)) => dot(u, v).map(Value::Number).ok_or(Failure::Shape(0, 1)),
+        (Value::Vector(_), _) => Err(Failure::Argument(1)),
+        _ => Err(Failure::Argument(0)),
+    }},
+    MatrixFunction { name: "cross", arity: 2, call: |args| match (&args[0], &args[1]) {
+        (Value::Vector(u), Value::Vector(v)) if u.len() != v.len() => Err(Failure::Shape(0, 1)),
+        (Value::Vector(u), Value::Vector(v)) => cross(u, v).map(Value::Vector).ok_or(Failure::Argument(0)),
+        (Value::Vector(_), _) => Err(Failure::Argument(1)),
+        _ => Err(Failure::Argument(0)),
+    }},
+];
+
+pub fn get_matrix_function(name: &str) -> Option<&'static MatrixFunction> {
+    MATRIX_FUNCTIONS.iter().find(|function| function.name == name)
+}
+
+/// Tells whether a name is a built-in constant or function, which neither assignments nor
+/// definitions may take.
+pub fn is_reserved(name: &str) -> bool {
+    get_constant(name).is_some()
+        || get_function(name).is_some()
+        || get_complex_function(name).is_some()
+        || get_decimal_function(name).is_some()
+        || get_date_function(name).is_some()
+        || get_matrix_function(name).is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_constants_by_name() {
+        assert!(matches!(get_constant("pi"), Some(Value::Number(value)) if value == consts::PI));
+        assert!(matches!(get_constant("π"), Some(Value::Number(value)) if value == consts::PI));
+        assert!(matches!(get_constant("inf"), Some(Value::Number(value)) if value.is_infinite()));
+        assert!(matches!(get_constant("nan"), Some(Value::Number(value)) if value.is_nan()));
+        assert!(get_constant("Pi").is_none());
+    }
+
+    #[test]
+    fn calls_functions_within_their_domain() {
+        let call = |name: &str, args: &[f64]| (get_function(name).unwrap().call)(args);
+        assert_eq!(call("sqrt", &[16.0]), Some(4.0));
+        assert_eq!(call("sqrt", &[-1.0]), None);
+        assert_eq!(call("log", &[8.0, 2.0]), Some(3.0));
+        assert_eq!(call("log", &[8.0, 1.0]), None);
+        assert_eq!(call("round", &[2.345, 2.0]), Some(2.35));
+        assert_eq!(call("max", &[1.0, 5.0, 3.0]), Some(5.0));
+        assert!(get_function("nope").is_none());
+    }
+
+    #[test]
+    fn accepts_counts_of_arguments_by_arity() {
+        assert!(Arity::Exact(1).accepts(1) && !Arity::Exact(1).accepts(2));
+        assert!(Arity::Range(1, 2).accepts(2) && !Arity::Range(1, 2).accepts(0));
+        assert!(Arity::AtLeast(1).accepts(9) && !Arity::AtLeast(1).accepts(0));
+        assert_eq!(Arity::Range(1, 2).to_string(), "1 to 2");
+    }
+
+    #[test]
+    fn calls_complex_functions() {
+        let call = |name: &str, arg: Complex64| (get_complex_function(name).unwrap().call)(arg);
+        assert_eq!(call("sqrt", Complex64::new(-4.0, 0.0)), Some(Complex64::new(0.0, 2.0)));
+        assert_eq!(call("abs", Complex64::new(3.0, 4.0)), Some(Complex64::new(5.0, 0.0)));
+        assert_eq!(call("conj", Complex64::new(1.0, 2.0)), Some(Complex64::new(1.0, -2.0)));
+        assert_eq!(call("ln", Complex64::new(0.0, 0.0)), None);
+    }
+}
@@ -0,0 +1,261 @@
+use crate::value::*;
+use std::fmt::{Display, Formatter};
+
+/// Pivots smaller than this are taken for zero when solving, the matrix being singular.
+const EPSILON: f64 = 1e-12;
+
+/**************************************************************************************************/
+/*                                             MATRIX                                             */
+/**************************************************************************************************/
+
+/// A matrix of floats, stored row after row.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Matrix {
+    pub rows: usize,
+    pub columns: usize,
+    pub elements: Vec<f64>,
+}
+
+impl Matrix {
+    /// Builds a matrix from rows of the same length, giving `None` for ragged rows.
+    pub fn from_rows(rows: Vec<Vec<f64>>) -> Option<Matrix> {
+        let columns = rows.first().map_or(0, |row| row.len());
+        if rows.iter().any(|row| row.len() != columns) {
+            return None;
+        }
+        Some(Matrix { rows: rows.len(), columns, elements: rows.concat() })
+    }
+
+    /// Reads a vector as a matrix of one column, so that `A * v` is a matrix product.
+    pub fn from_column(vector: &[f64]) -> Matrix {
+        Matrix { rows: vector.len(), columns: 1, elements: vector.to_vec() }
+    }
+
+    /// Reads a vector as a matrix of one row, so that `v * A` is a matrix product.
+    pub fn from_row(vector: &[f64]) -> Matrix {
+        Matrix { rows: 1, columns: vector.len(), elements: vector.to_vec() }
+    }
+
+    pub fn identity(size: usize) -> Matrix {
+        let mut elements = vec![0.0; size * size];
+        for i in 0..size {
+            elements[i * size + i] = 1.0;
+        }
+        Matrix { rows: size, columns: size, elements }
+    }
+
+    pub fn get(&self, row: usize, column: usize) -> f64 {
+        self.elements[row * self.columns + column]
+    }
+
+    pub fn get_rows(&self) -> Vec<Vec<f64>> {
+        self.elements.chunks(self.columns.max(1)).map(|row| row.to_vec()).collect()
+    }
+
+    /// Writes the shape as `2x3`, rows first.
+    pub fn get_shape(&self) -> String {
+        format!("{}x{}", self.rows, self.columns)
+    }
+
+    pub fn is_square(&self) -> bool {
+        self.rows == self.columns
+    }
+
+    pub fn transpose(&self) -> Matrix {
+        let mut elements = Vec::with_capacity(self.elements.len());
+        for column in 0..self.columns {
+            for row in 0..self.rows {
+                elements.push(self.get(row, column));
+            }
+        }
+        Matrix { rows: self.columns, columns: self.rows, elements }
+    }
+
+    /// Applies an operation to every element, as to multiply by a number.
+    pub fn map<F>(&self, operation: F) -> Matrix where F: Fn(f64) -> f64 {
+        Matrix { elements: self.elements.iter().map(|x| operation(*x)).collect(), ..*self }
+    }
+
+    /// Combines the elements at the same place, giving `None` when the shapes differ.
+    pub fn zip<F>(&self, other: &Matrix, operation: F) -> Option<Matrix> where F: Fn(f64, f64) -> f64 {
+        if self.rows != other.rows || self.columns != other.columns {
+            return None;
+        }
+        let elements = self.elements.iter().zip(&other.elements).map(|(x, y)| operation(*x, *y)).collect();
+        Some(Matrix { elements, ..*self })
+    }
+
+    /// The matrix product, giving `None` unless the left operand has as many columns as the right
+    /// one has rows.
+    pub fn multiply(&self, other: &Matrix) -> Option<Matrix> {
+        if self.columns != other.rows {
+            return None;
+        }
+        let mut elements = Vec::with_capacity(self.rows * other.columns);
+        for row in 0..self.rows {
+            for column in 0..other.columns {
+                elements.push((0..self.columns).map(|k| self.get(row, k) * other.get(k, column)).sum());
+            }
+        }
+        Some(Matrix { rows: self.rows, columns: other.columns, elements })
+    }
+
+    /// Raises a square matrix to an integer power by squaring, negative powers being powers of the
+    /// inverse.
+    pub fn power(&self, exponent: i32) -> Option<Matrix> {
+        let mut base = match exponent < 0 {
+            true => self.inverse()?,
+            false => self.clone(),
+        };
+        let mut result = Matrix::identity(self.rows);
+        let mut exponent = exponent.unsigned_abs();
+        while exponent > 0 {
+            if exponent & 1 == 1 {
+                result = result.multiply(&base)?;
+            }
+            base = base.multiply(&base)?;
+            exponent >>= 1;
+        }
+        Some(result)
+    }
+
+    /// Computes the determinant of a square matrix by Gaussian elimination.
+    pub fn determinant(&self) -> Option<f64> {
+        if !self.is_square() {
+            return None;
+        }
+        let mut rows = self.get_rows();
+        let mut determinant = 1.0;
+        for column in 0..self.columns {
+            let pivot = Matrix::find_pivot(&rows, column);
+            if rows[pivot][column] == 0.0 {
+                return Some(0.0);
+            }
+            if pivot != column {
+                rows.swap(pivot, column);
+                determinant = -determinant;
+            }
+            determinant *= rows[column][column];
+            Matrix::eliminate(&mut rows, column);
+        }
+        Some(determinant)
+    }
+
+    pub fn inverse(&self) -> Option<Matrix> {
+        self.solve(&Matrix::identity(self.rows))
+    }
+
+    /// Solves `A X = B` by Gauss-Jordan elimination with partial pivoting, giving `None` when `A` is
+    /// not square, when `B` doesn't have as many rows, or when `A` is singular.
+    pub fn solve(&self, other: &Matrix) -> Option<Matrix> {
+        if !self.is_square() || self.rows != other.rows {
+            return None;
+        }
+        let mut rows: Vec<Vec<f64>> = self.get_rows().into_iter()
+            .zip(other.get_rows())
+            .map(|(row, right)| [row, right].concat())
+            .collect();
+        // Relative to the largest element, so that the test doesn't depend on the scale of `A`.
+        let scale = self.elements.iter().fold(0.0_f64, |max, x| max.max(x.abs()));
+        for column in 0..self.columns {
+            let pivot = Matrix::find_pivot(&rows, column);
+            if rows[pivot][column].abs() <= EPSILON * scale {
+                return None;
+            }
+            rows.swap(pivot, column);
+            let divisor = rows[column][column];
+            rows[column].iter_mut().for_each(|x| *x /= divisor);
+            for row in 0..self.rows {
+                if row != column {
+                    let factor = rows[row][column];
+                    for k in 0..rows[row].len() {
+                        rows[row][k] -= factor * rows[column][k];
+                    }
+                }
+            }
+        }
+        let elements = rows.into_iter().flat_map(|row| row[self.columns..].to_vec()).collect();
+        Some(Matrix { rows: self.rows, columns: other.columns, elements })
+    }
+
+    /// Finds the row, from the diagonal down, with the largest element in a column.
+    fn find_pivot(rows: &[Vec<f64>], column: usize) -> usize {
+        (column..rows.len())
+            .max_by(|a, b| rows[*a][column].abs().total_cmp(&rows[*b][column].abs()))
+            .unwrap()
+    }
+
+    /// Subtracts the pivot row from the rows below it, clearing its column.
+    fn eliminate(rows: &mut [Vec<f64>], column: usize) {
+        for row in column + 1..rows.len() {
+            let factor = rows[row][column] / rows[column][column];
+            for k in column..rows[row].len() {
+                rows[row][k] -= factor * rows[column][k];
+            }
+        }
+    }
+}
+
+impl Display for Matrix {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let rows: Vec<String> = self.get_rows().iter().map(|row| format_vector(row)).collect();
+        write!(f, "[{}]", rows.join(", "))
+    }
+}
+
+/// Writes a vector as `[1, 2, 3]`, with the same number format as the other values.
+pub fn format_vector(vector: &[f64]) -> String {
+    let elements: Vec<String> = vector.iter().map(|x| Value::Number(*x).to_string()).collect();
+    format!("[{}]", elements.join(", "))
+}
+
+/**************************************************************************************************/
+/*                                            VECTORS                                             */
+/**************************************************************************************************/
+
+pub fn dot(u: &[f64], v: &[f64]) -> Option<f64> {
+    match u.len() == v.len() {
+        true => Some(u.iter().zip(v).map(|(x, y)| x * y).sum()),
+        false => None,
+    }
+}
+
+pub fn cross(u: &[f64], v: &[f64]) -> Option<Vec<f64>> {
+    match (u, v) {
+        ([a1, a2, a3], [b1, b2, b3]) => Some(vec![a2 * b3 - a3 * b2, a3 * b1 - a1 * b3, a1 * b2 - a2 * b1]),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matrix(rows: &[&[f64]]) -> Matrix {
+        Matrix::from_rows(rows.iter().map(|row| row.to_vec()).collect()).unwrap()
+    }
+
+    #[test]
+    fn multiplies_and_inverts_matrices() {
+        let a = matrix(&[&[1.0, 2.0], &[3.0, 4.0]]);
+        assert_eq!(a.multiply(&matrix(&[&[5.0, 6.0], &[7.0, 8.0]])), Some(matrix(&[&[19.0, 22.0], &[43.0, 50.0]])));
+        assert!(a.multiply(&matrix(&[&[1.0, 2.0, 3.0]])).is_none());
+        assert_eq!(a.transpose(), matrix(&[&[1.0, 3.0], &[2.0, 4.0]]));
+        assert_eq!(a.determinant(), Some(-2.0));
+        let identity = a.multiply(&a.inverse().unwrap()).unwrap();
+        assert!(identity.elements.iter().zip(Matrix::identity(2).elements).all(|(x, y)| (x - y).abs() < 1e-12));
+        assert_eq!(a.power(2), a.multiply(&a));
+        assert!(matrix(&[&[1.0, 2.0], &[2.0, 4.0]]).inverse().is_none());
+        assert!(Matrix::from_rows(vec![vec![1.0], vec![1.0, 2.0]]).is_none());
+        assert_eq!(a.to_string(), "[[1, 2], [3, 4]]");
+        assert_eq!(matrix(&[&[1.0, 2.0, 3.0]]).get_shape(), "1x3");
+    }
+
+    #[test]
+    fn multiplies_vectors() {
+        assert_eq!(dot(&[1.0, 2.0, 3.0], &[4.0, 5.0, 6.0]), Some(32.0));
+        assert_eq!(dot(&[1.0], &[1.0, 2.0]), None);
+        assert_eq!(cross(&[1.0, 0.0, 0.0], &[0.0, 1.0, 0.0]), Some(vec![0.0, 0.0, 1.0]));
+        assert_eq!(cross(&[1.0, 0.0], &[0.0, 1.0]), None);
+    }
+}
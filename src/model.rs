@@ -1,31 +1,90 @@
+use std::borrow::Cow;
 use std::fmt::{Debug, Display, Formatter};
 use either::*;
 
 /**************************************************************************************************/
 /*                                              AST                                               */
 /**************************************************************************************************/
+#[allow(clippy::upper_case_acronyms)]
 pub trait AST {
     fn get_type(&self) -> String;
     fn get_start(&self) -> Position;
     fn get_next(&self) -> Position;
-    fn get_tokens(&self) -> Vec<Token>;
-    fn get_json(&self, with_position: bool) -> String;
+    fn get_tokens(&self) -> Vec<Token<'_>>;
+    fn get_json(&self, offsets: Option<Offsets>) -> String;
     fn is(&self, value: &str) -> bool;
 }
 
 /**************************************************************************************************/
 /*                                           POSITION                                             */
 /**************************************************************************************************/
-#[derive(Debug, Copy, Clone, PartialEq)]
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
 pub struct Position {
+    /// Offset in `char`s.
     pub cursor: usize,
+    /// Offset in bytes of UTF-8, to slice the source in Rust.
+    pub byte: usize,
+    /// Offset in code units of UTF-16, to slice the source in JavaScript.
+    pub utf16: usize,
     pub line: usize,
+    /// Column in `char`s.
     pub column: usize,
 }
 
 impl Position {
-    pub fn get_json(&self) -> String {
-        format!(r#"{{"cursor":{},"line":{},"column":{}}}"#, self.cursor, self.line, self.column)
+    /// Moves past a character. `\r\n` is a single line break, as are `\n` and `\r` alone, so
+    /// `previous` tells whether `\n` ends a line already broken by `\r`.
+    pub fn advance(&self, c: char, previous: Option<char>) -> Position {
+        let (line, column) = match (c, previous) {
+            ('\n', Some('\r')) => (self.line, 0),
+            ('\n' | '\r', _) => (self.line + 1, 0),
+            _ => (self.line, self.column + 1),
+        };
+        Position {
+            cursor: self.cursor + 1,
+            byte: self.byte + c.len_utf8(),
+            utf16: self.utf16 + c.len_utf16(),
+            line,
+            column,
+        }
+    }
+
+    pub fn get_json(&self, offsets: Offsets) -> String {
+        let mut str = String::from("");
+        if offsets.char { str = format!(r#"{}"cursor":{},"#, str, self.cursor); }
+        if offsets.byte { str = format!(r#"{}"byte":{},"#, str, self.byte); }
+        if offsets.utf16 { str = format!(r#"{}"utf16":{},"#, str, self.utf16); }
+        format!(r#"{{{}"line":{},"column":{}}}"#, str, self.line, self.column)
+    }
+}
+
+/// The offsets written in the JSON of positions, in `char`s only unless chosen otherwise.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Offsets {
+    pub char: bool,
+    pub byte: bool,
+    pub utf16: bool,
+}
+
+impl Default for Offsets {
+    fn default() -> Self {
+        Offsets { char: true, byte: false, utf16: false }
+    }
+}
+
+impl Offsets {
+    /// Reads a comma-separated list of `char`, `byte` and `utf16`.
+    pub fn parse(text: &str) -> Option<Offsets> {
+        let mut offsets = Offsets { char: false, byte: false, utf16: false };
+        for name in text.split(',').map(str::trim) {
+            match name {
+                "char" => offsets.char = true,
+                "byte" => offsets.byte = true,
+                "utf16" => offsets.utf16 = true,
+                _ => return None,
+            }
+        }
+        Some(offsets)
     }
 }
 
@@ -69,125 +128,187 @@ fn is_equal_value(token: &Token, chr: Option<char>, str: Option<&str>) -> bool {
     }
 }
 
+/// The whitespace and line breaks around a token, which the parser skips but keeps so that the
+/// source text can be rebuilt from the tree. Whitespace belongs to the token after it, except at
+/// the end of the input where it trails the last token.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Trivia<'a> {
+    pub leading: Vec<Token<'a>>,
+    pub trailing: Vec<Token<'a>>,
+}
+
+/// A token borrows its text from the source, until `into_owned` copies it to outlive the source as
+/// the body of a user-defined function does.
 #[derive(Debug, Clone, PartialEq)]
-pub enum Token {
-    Identifier(String, Position, Position),
-    Number(String, Position, Position),
-    Operator(String, Position, Position),
-    Punctuation(char, Position, Position),
-    Whitespace(String, Position, Position),
+pub enum Token<'a> {
+    Identifier(Cow<'a, str>, Position, Position, Trivia<'a>),
+    Number(Cow<'a, str>, Position, Position, Trivia<'a>),
+    Operator(Cow<'a, str>, Position, Position, Trivia<'a>),
+    Punctuation(char, Position, Position, Trivia<'a>),
+    Whitespace(Cow<'a, str>, Position, Position, Trivia<'a>),
 }
 
-impl Token {
+impl<'a> Token<'a> {
+    pub fn into_owned(self) -> Token<'static> {
+        let own = |tokens: Vec<Token>| tokens.into_iter().map(Token::into_owned).collect();
+        let own_trivia = |trivia: Trivia| Trivia { leading: own(trivia.leading), trailing: own(trivia.trailing) };
+        match self {
+            Token::Identifier(value, start, next, trivia) => Token::Identifier(Cow::Owned(value.into_owned()), start, next, own_trivia(trivia)),
+            Token::Number(value, start, next, trivia) => Token::Number(Cow::Owned(value.into_owned()), start, next, own_trivia(trivia)),
+            Token::Operator(value, start, next, trivia) => Token::Operator(Cow::Owned(value.into_owned()), start, next, own_trivia(trivia)),
+            Token::Punctuation(value, start, next, trivia) => Token::Punctuation(value, start, next, own_trivia(trivia)),
+            Token::Whitespace(value, start, next, trivia) => Token::Whitespace(Cow::Owned(value.into_owned()), start, next, own_trivia(trivia)),
+        }
+    }
+
     pub fn get_value(&self) -> String {
         match self {
-            Token::Identifier(value, _, _) => value.to_string(),
-            Token::Number(value, _, _) => value.to_string(),
-            Token::Operator(value, _, _) => value.to_string(),
-            Token::Punctuation(value, _, _) => value.to_string(),
-            Token::Whitespace(value, _, _) => value.to_string(),
+            Token::Identifier(value, _, _, _) => value.to_string(),
+            Token::Number(value, _, _, _) => value.to_string(),
+            Token::Operator(value, _, _, _) => value.to_string(),
+            Token::Punctuation(value, _, _, _) => value.to_string(),
+            Token::Whitespace(value, _, _, _) => value.to_string(),
         }
     }
 
+    pub fn get_trivia(&self) -> &Trivia<'a> {
+        match self {
+            Token::Identifier(_, _, _, trivia) => trivia,
+            Token::Number(_, _, _, trivia) => trivia,
+            Token::Operator(_, _, _, trivia) => trivia,
+            Token::Punctuation(_, _, _, trivia) => trivia,
+            Token::Whitespace(_, _, _, trivia) => trivia,
+        }
+    }
+
+    pub fn get_trivia_mut(&mut self) -> &mut Trivia<'a> {
+        match self {
+            Token::Identifier(_, _, _, trivia) => trivia,
+            Token::Number(_, _, _, trivia) => trivia,
+            Token::Operator(_, _, _, trivia) => trivia,
+            Token::Punctuation(_, _, _, trivia) => trivia,
+            Token::Whitespace(_, _, _, trivia) => trivia,
+        }
+    }
+
+    /// Gives the source text of the token with its trivia, where `get_value` gives it alone.
+    pub fn get_text(&self) -> String {
+        let trivia = self.get_trivia();
+        let leading: String = trivia.leading.iter().map(|token| token.get_value()).collect();
+        let trailing: String = trivia.trailing.iter().map(|token| token.get_value()).collect();
+        format!("{}{}{}", leading, self.get_value(), trailing)
+    }
+
     pub fn is_identifier(&self, value: Option<&str>) -> bool {
         match self {
-            Token::Identifier(_, _, _) => is_equal_value(self, None, value),
+            Token::Identifier(_, _, _, _) => is_equal_value(self, None, value),
             _ => false,
         }
     }
 
     pub fn is_number(&self, value: Option<&str>) -> bool {
         match self {
-            Token::Number(_, _, _) => is_equal_value(self, None, value),
+            Token::Number(_, _, _, _) => is_equal_value(self, None, value),
             _ => false,
         }
     }
 
     pub fn is_operator(&self, value: Option<&str>) -> bool {
         match self {
-            Token::Operator(_, _, _) => is_equal_value(self, None, value),
+            Token::Operator(_, _, _, _) => is_equal_value(self, None, value),
             _ => false,
         }
     }
 
     pub fn is_punctuation(&self, value: Option<char>) -> bool {
         match self {
-            Token::Punctuation(_, _, _) => is_equal_value(self, value, None),
+            Token::Punctuation(_, _, _, _) => is_equal_value(self, value, None),
             _ => false,
         }
     }
     pub fn is_one_of_punctuation(&self, value: Vec<char>) -> bool {
         match self {
-            Token::Punctuation(_, _, _) => is_one_of(self, Some(value), None),
+            Token::Punctuation(_, _, _, _) => is_one_of(self, Some(value), None),
             _ => false,
         }
     }
 
     pub fn is_whitespace(&self, value: Option<&str>) -> bool {
         match self {
-            Token::Whitespace(_, _, _) => is_equal_value(self, None, value),
+            Token::Whitespace(_, _, _, _) => is_equal_value(self, None, value),
             _ => false,
         }
     }
 }
 
-impl AST for Token {
+impl AST for Token<'_> {
     fn get_type(&self) -> String {
         match self {
-            Token::Identifier(_, _, _) => String::from("Identifier"),
-            Token::Number(_, _, _) => String::from("Number"),
-            Token::Operator(_, _, _) => String::from("Operator"),
-            Token::Punctuation(_, _, _) => String::from("Punctuation"),
-            Token::Whitespace(_, _, _) => String::from("Whitespace"),
+            Token::Identifier(_, _, _, _) => String::from("Identifier"),
+            Token::Number(_, _, _, _) => String::from("Number"),
+            Token::Operator(_, _, _, _) => String::from("Operator"),
+            Token::Punctuation(_, _, _, _) => String::from("Punctuation"),
+            Token::Whitespace(_, _, _, _) => String::from("Whitespace"),
         }
     }
 
     fn get_start(&self) -> Position {
         match self {
-            Token::Identifier(_, start, _) => start.clone(),
-            Token::Number(_, start, _) => start.clone(),
-            Token::Operator(_, start, _) => start.clone(),
-            Token::Punctuation(_, start, _) => start.clone(),
-            Token::Whitespace(_, start, _) => start.clone(),
+            Token::Identifier(_, start, _, _) => *start,
+            Token::Number(_, start, _, _) => *start,
+            Token::Operator(_, start, _, _) => *start,
+            Token::Punctuation(_, start, _, _) => *start,
+            Token::Whitespace(_, start, _, _) => *start,
         }
     }
 
     fn get_next(&self) -> Position {
         match self {
-            Token::Identifier(_, _, next) => next.clone(),
-            Token::Number(_, _, next) => next.clone(),
-            Token::Operator(_, _, next) => next.clone(),
-            Token::Punctuation(_, _, next) => next.clone(),
-            Token::Whitespace(_, _, next) => next.clone(),
+            Token::Identifier(_, _, next, _) => *next,
+            Token::Number(_, _, next, _) => *next,
+            Token::Operator(_, _, next, _) => *next,
+            Token::Punctuation(_, _, next, _) => *next,
+            Token::Whitespace(_, _, next, _) => *next,
         }
     }
 
-    fn get_tokens(&self) -> Vec<Token> {
+    fn get_tokens(&self) -> Vec<Token<'_>> {
         vec![self.clone()]
     }
 
-    fn get_json(&self, with_position: bool) -> String {
+    fn get_json(&self, offsets: Option<Offsets>) -> String {
         let value = self.get_value()
-            .replace("\r\n", "\\n")
+            .replace("\\", "\\\\")
             .replace("\n", "\\n")
-            .replace("\r", "\\n")
+            .replace("\r", "\\r")
             .replace("\t", "\\t")
             .replace("\"", "\\\"")
         ;
 
-        if with_position {
+        // The whitespace around the token, so that the source can be rebuilt from the JSON of a tree.
+        let mut trivia = String::from("");
+        for (name, tokens) in [("leading", &self.get_trivia().leading), ("trailing", &self.get_trivia().trailing)] {
+            if !tokens.is_empty() {
+                let json: Vec<String> = tokens.iter().map(|token| token.get_json(offsets)).collect();
+                trivia = format!(r#"{},"{}":[{}]"#, trivia, name, json.join(","));
+            }
+        }
+
+        if let Some(offsets) = offsets {
             format!(
-                r#"{{"type":"{}","value":"{}","start":{},"next":{}}}"#,
+                r#"{{"type":"{}","value":"{}","start":{},"next":{}{}}}"#,
                 self.get_type(),
                 value,
-                self.get_start().get_json(),
-                self.get_next().get_json(),
+                self.get_start().get_json(offsets),
+                self.get_next().get_json(offsets),
+                trivia,
             )
         } else {
             format!(
-                r#"{{"type":"{}","value":"{}"}}"#,
+                r#"{{"type":"{}","value":"{}"{}}}"#,
                 self.get_type(),
                 value,
+                trivia,
             )
         }
     }
@@ -197,7 +318,7 @@ impl AST for Token {
     }
 }
 
-impl Display for Token {
+impl Display for Token<'_> {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}({})", self.get_type(), self.get_value())
     }
@@ -206,44 +327,82 @@ impl Display for Token {
 /**************************************************************************************************/
 /*                                             NODE                                               */
 /**************************************************************************************************/
-pub type NodeItem = Either<Token, Node>;
-pub type NodeValue = Vec<NodeItem>;
-
-#[derive(Debug, Clone)]
-pub struct Node {
-    kind: Box<String>,
-    value: Box<NodeValue>,
+pub type NodeItem<'a> = Either<Token<'a>, Node<'a>>;
+pub type NodeValue<'a> = Vec<NodeItem<'a>>;
+
+#[derive(Debug)]
+pub struct Node<'a> {
+    kind: String,
+    value: Box<NodeValue<'a>>,
+    /// Where the first item starts and the last one ends, which the abstract syntax tree widens to
+    /// the parentheses around the node.
+    start: Position,
+    next: Position,
 }
 
-impl Node {
-    pub fn create(kind: &str, value: NodeValue) -> Node {
+impl<'a> Node<'a> {
+    pub fn create(kind: &str, value: NodeValue<'a>) -> Node<'a> {
+        let (start, next) = match (value.first(), value.last()) {
+            (Some(first), Some(last)) => (for_both!(first, item => item.get_start()), for_both!(last, item => item.get_next())),
+            _ => (Position::default(), Position::default()),
+        };
         Node {
-            kind: Box::new(kind.to_string()),
-            value: Box::new(value)
+            kind: kind.to_string(),
+            value: Box::new(value),
+            start,
+            next,
         }
     }
 
-    pub fn create_atom(token: Token) -> Node {
+    pub fn create_atom(token: Token<'a>) -> Node<'a> {
         Node::create("atom", vec![Left(token)])
     }
 
-    pub fn get_text(&self) -> String {
-        let mut tokens = self.get_tokens();
-        let mut str = String::from("");
-        while 0 < tokens.len() {
-            tokens.rotate_left(1);
-            if let Some(token) = tokens.pop() {
-                str.push_str(&token.get_value());
+    pub fn into_owned(self) -> Node<'static> {
+        let value = self.rebuild(|_, token| Some(token.clone().into_owned()), Node::assemble);
+        Node { kind: self.kind.clone(), value: Box::new(value), start: self.start, next: self.next }
+    }
+
+    /// Rebuilds the items of the node bottom-up with a stack rather than by recursion, so that a
+    /// long chain such as `1 + 1 + … + 1` can't overflow the stack. `token` maps the tokens of a
+    /// node, leaving out those it gives `None` for, and `node` puts a node back together from its
+    /// mapped items.
+    fn rebuild<'b, T, N>(&self, token: T, node: N) -> NodeValue<'b>
+        where T: Fn(&Node<'a>, &Token<'a>) -> Option<Token<'b>>, N: Fn(&Node<'a>, NodeValue<'b>) -> NodeItem<'b> {
+        let mut stack: Vec<(&Node<'a>, std::slice::Iter<NodeItem<'a>>, NodeValue<'b>)> = vec![(self, self.value.iter(), vec![])];
+        loop {
+            let (parent, items, value) = stack.last_mut().unwrap();
+            match items.next() {
+                Some(Left(item)) => value.extend(token(parent, item).map(Left)),
+                Some(Right(child)) => stack.push((child, child.value.iter(), vec![])),
+                None => {
+                    let (child, _, value) = stack.pop().unwrap();
+                    match stack.last_mut() {
+                        None => return value,
+                        Some((_, _, parent)) => parent.push(node(child, value)),
+                    }
+                },
             }
         }
-        String::from(str)
     }
 
-    pub fn get_token(&self) -> Option<Token> {
+    fn assemble<'b>(node: &Node<'a>, value: NodeValue<'b>) -> NodeItem<'b> {
+        Right(Node { kind: node.kind.clone(), value: Box::new(value), start: node.start, next: node.next })
+    }
+
+    pub fn get_text(&self) -> String {
+        self.get_tokens().iter().map(|token| token.get_text()).collect()
+    }
+
+    /// Gives the text of the tokens without their trivia, as `km/h` for `km / h`.
+    pub fn get_code(&self) -> String {
+        self.get_tokens().iter().map(|token| token.get_value()).collect()
+    }
+
+    pub fn get_token(&self) -> Option<Token<'a>> {
         let kind = self.get_type();
         if kind.eq("atom") {
-            let value = self.get_value();
-            if let Left(token) = value.iter().nth(0).unwrap() {
+            if let Left(token) = self.value.first().unwrap() {
                 Some(token.clone())
             } else {
                 None
@@ -253,83 +412,110 @@ impl Node {
         }
     }
 
-    pub fn get_value(&self) -> NodeValue {
-        let mut value: NodeValue = vec![];
+    /// Derives the abstract syntax tree from this concrete one, which keeps every token of the
+    /// source. Groups give way to the expression in their parentheses, and the `=`, `?` and `:`
+    /// separating the parts of assignments, definitions and conditionals are left out. Calls and
+    /// lists keep their punctuation, which tells their items apart.
+    pub fn get_ast(&self) -> Node<'a> {
+        let is_separator = |node: &Node, token: &Token| {
+            matches!(node.kind.as_str(), "Assign" | "Define" | "Conditional")
+                && (token.is_operator(Some("=")) || token.is_operator(Some("?")) || token.is_punctuation(Some(':')))
+        };
+        let value = self.rebuild(
+            |node, token| if is_separator(node, token) { None } else { Some(token.clone()) },
+            |node, mut value| {
+                if node.kind != "Group" || value.len() != 3 {
+                    return Right(Node::create(&node.kind, value));
+                }
+                match value.swap_remove(1) {
+                    Left(token) => Left(token),
+                    Right(mut inner) => {
+                        inner.start = node.start;
+                        inner.next = node.next;
+                        Right(inner)
+                    },
+                }
+            },
+        );
+        Node::create(&self.kind, value)
+    }
+
+    /// Borrows the items of the node, which `get_value` copies.
+    pub fn children(&self) -> &[NodeItem<'a>] {
+        &self.value
+    }
+
+    pub fn get_value(&self) -> NodeValue<'a> {
+        let mut value: NodeValue<'a> = vec![];
         for item in self.value.iter() {
             match item {
                 Left(token) => value.push(Left(token.clone())),
                 Right(node) => value.push(Right(node.clone())),
             }
         }
-        return value;
+        value
     }
 }
 
-impl AST for Node {
+impl AST for Node<'_> {
     fn get_type(&self) -> String {
-        return *self.kind.clone();
+        self.kind.clone()
     }
 
     fn get_start(&self) -> Position {
-        let item = self.value.iter().nth(0).unwrap();
-        if let Left(token) = item {
-            token.get_start()
-        } else if let Right(node) = item {
-            node.get_start()
-        } else {
-            panic!("Unknown item type")
-        }
+        self.start
     }
 
     fn get_next(&self) -> Position {
-        let item = self.value.iter().nth(0).unwrap();
-        if let Left(token) = item {
-            token.get_next()
-        } else if let Right(node) = item {
-            node.get_next()
-        } else {
-            panic!("Unknown item type")
-        }
+        self.next
     }
 
-    fn get_tokens(&self) -> Vec<Token> {
+    fn get_tokens(&self) -> Vec<Token<'_>> {
         let mut tokens: Vec<Token> = vec![];
-        for item in self.get_value().iter() {
-            if let Left(token) = item {
-                tokens.push(token.clone());
-            } else if let Right(node) = item {
-                tokens.append(&mut node.get_tokens());
+        let mut stack = vec![self.value.iter()];
+        while let Some(items) = stack.last_mut() {
+            match items.next() {
+                None => { stack.pop(); },
+                Some(Left(token)) => tokens.push(token.clone()),
+                Some(Right(node)) => stack.push(node.value.iter()),
             }
         }
-        return tokens;
-    }
-
-    fn get_json(&self, with_position: bool) -> String {
-        let mut str = String::from("");
-        for item in self.get_value() {
-            if let Left(token) = item {
-                str = format!("{},{}", str, token.get_json(with_position))
-            } else if let Right(node) = item {
-                str = format!("{},{}", str, node.get_json(with_position))
+        tokens
+    }
+
+    /// Writes the tree with a stack rather than by recursion, like `rebuild`.
+    fn get_json(&self, offsets: Option<Offsets>) -> String {
+        let mut str = format!(r#"{{"type":"{}","value":["#, self.get_type());
+        let mut stack = vec![(self, self.value.iter())];
+        while let Some((node, items)) = stack.last_mut() {
+            let (node, first) = (*node, items.len() == node.value.len());
+            match items.next() {
+                Some(item) => {
+                    if !first {
+                        str.push(',');
+                    }
+                    match item {
+                        Left(token) => str.push_str(&token.get_json(offsets)),
+                        Right(child) => {
+                            str.push_str(&format!(r#"{{"type":"{}","value":["#, child.get_type()));
+                            stack.push((child, child.value.iter()));
+                        },
+                    }
+                },
+                None => {
+                    stack.pop();
+                    match offsets {
+                        Some(offsets) => str.push_str(&format!(
+                            r#"],"start":{},"next":{}}}"#,
+                            node.get_start().get_json(offsets),
+                            node.get_next().get_json(offsets),
+                        )),
+                        None => str.push_str("]}"),
+                    }
+                },
             }
         }
-        let str = str.trim_start_matches(&[',']);
-
-        if with_position {
-            format!(
-                r#"{{"type":"{}","value":[{}],"start":{},"next":{}}}"#,
-                self.get_type(),
-                str,
-                self.get_start().get_json(),
-                self.get_next().get_json(),
-            )
-        } else {
-            format!(
-                r#"{{"type":"{}","value":[{}]}}"#,
-                self.get_type(),
-                str,
-            )
-        }
+        str
     }
 
     fn is(&self, kind: &str) -> bool {
@@ -341,9 +527,26 @@ impl AST for Node {
     }
 }
 
-impl Display for Node {
+impl Clone for Node<'_> {
+    fn clone(&self) -> Self {
+        let value = self.rebuild(|_, token| Some(token.clone()), Node::assemble);
+        Node { kind: self.kind.clone(), value: Box::new(value), start: self.start, next: self.next }
+    }
+}
+
+impl Drop for Node<'_> {
+    /// Takes the nodes below apart with a stack rather than by recursion, like `rebuild`.
+    fn drop(&mut self) {
+        let mut nodes: Vec<Node> = self.value.drain(..).filter_map(Either::right).collect();
+        while let Some(mut node) = nodes.pop() {
+            nodes.extend(node.value.drain(..).filter_map(Either::right));
+        }
+    }
+}
+
+impl Display for Node<'_> {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}({})", self.get_type(), self.get_text())
+        write!(f, "{}({})", self.get_type(), self.get_code())
     }
 }
 
@@ -351,24 +554,81 @@ impl Display for Node {
 /*                                             ERROR                                              */
 /**************************************************************************************************/
 
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Debug, Clone)]
 pub enum Error {
+    DivisionByZero(Position),
+    DuplicateParameter(Token<'static>),
     EOF,
+    IncompatibleUnits(String, String, Position, Position),
+    InvalidDate(String, Position),
+    InvalidArity(String, String, usize, Position, Position),
+    InvalidNumber(Token<'static>),
+    MalformedExponent(String, Position),
+    MalformedNumber(String, Position),
+    NegativeExponent(Position),
+    /// The expression nests deeper than the parser allows, as `((((…))))` with thousands of
+    /// parentheses.
+    NestingLimit(usize, Position),
     NoBlockEnd(char, Position),
+    OutOfDomain(String, Position, Position),
+    OutOfRange(String, Position),
+    Overflow(String, Position),
+    RecursionLimit(String, usize, Position, Position),
+    /// An assignment or a definition takes the name of a built-in constant or function, as
+    /// `pi = 3` or `sqrt(x) = x`.
+    ReservedName(Token<'static>),
+    ShapeMismatch(String, Position, Position, String, Position, Position),
     UnexpectedCharacter(char, char, Position),
     UnexpectedEOF(Position),
-    UnexpectedItem(NodeItem),
-    UnexpectedToken(Token),
+    UnexpectedItem(NodeItem<'static>),
+    UnexpectedToken(Token<'static>),
+    UnexpectedValue(String, Position),
+    UnknownIdentifier(Token<'static>),
+    UnknownRate(String, String, Position, Position),
+    UnknownUnit(Token<'static>),
     UnprocessableCharacter(char, Position),
 }
 
 impl Display for Error {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
+            Error::DivisionByZero(pos)
+                => write!(f, "Division by zero in {}.", pos),
+            Error::DuplicateParameter(token)
+                => write!(f, "Duplicate parameter '{}' in {}.", token.get_value(), token.get_start()),
             Error::EOF
                 => write!(f, "End of file"),
+            Error::IncompatibleUnits(left, right, start, next)
+                => write!(f, "Incompatible units '{}' and '{}' in {}..{}.", left, right, start, next),
+            Error::InvalidDate(text, pos)
+                => write!(f, "Invalid date or time '{}' in {}.", text, pos),
+            Error::InvalidArity(name, expected, given, start, next)
+                => write!(f, "Function '{}' expects {} argument(s), got {} in {}..{}.", name, expected, given, start, next),
+            Error::InvalidNumber(token)
+                => write!(f, "Invalid number {} in {}.", token, token.get_start()),
+            Error::MalformedExponent(number, pos)
+                => write!(f, "Malformed exponent in number '{}' in {}, expected digits.", number, pos),
+            Error::MalformedNumber(number, pos)
+                => write!(f, "Malformed number '{}' in {}.", number, pos),
+            Error::NegativeExponent(pos)
+                => write!(f, "Negative exponent in integer power in {}.", pos),
+            Error::NestingLimit(limit, pos)
+                => write!(f, "Expression nests deeper than {} levels in {}.", limit, pos),
             Error::NoBlockEnd(c, pos)
                 => write!(f, "Block '{}' is not ended in {}", c, pos),
+            Error::OutOfDomain(name, start, next)
+                => write!(f, "Argument out of the domain of '{}' in {}..{}.", name, start, next),
+            Error::OutOfRange(value, pos)
+                => write!(f, "Value {} is out of range in {}.", value, pos),
+            Error::Overflow(integer_type, pos)
+                => write!(f, "Arithmetic overflow for {} in {}.", integer_type, pos),
+            Error::RecursionLimit(name, limit, start, next)
+                => write!(f, "Call to '{}' exceeds the recursion limit of {} in {}..{}.", name, limit, start, next),
+            Error::ReservedName(token)
+                => write!(f, "Can't redefine built-in '{}' in {}.", token.get_value(), token.get_start()),
+            Error::ShapeMismatch(left, start, next, right, other_start, other_next)
+                => write!(f, "Mismatched shapes {} in {}..{} and {} in {}..{}.", left, start, next, right, other_start, other_next),
             Error::UnexpectedCharacter(expected, value, pos)
                 => write!(f, "Expected token '{}' got '{}' in {}.", expected, value, pos),
             Error::UnexpectedEOF(pos)
@@ -383,6 +643,14 @@ impl Display for Error {
                 },
             Error::UnexpectedToken(token)
                 => write!(f, "Unexpected token {} in {}.", token, token.get_start()),
+            Error::UnexpectedValue(kind, pos)
+                => write!(f, "Unexpected {} value in {}.", kind, pos),
+            Error::UnknownIdentifier(token)
+                => write!(f, "Unknown identifier '{}' in {}.", token.get_value(), token.get_start()),
+            Error::UnknownRate(from, to, start, next)
+                => write!(f, "No exchange rate from '{}' to '{}' in {}..{}.", from, to, start, next),
+            Error::UnknownUnit(token)
+                => write!(f, "Unknown unit '{}' in {}.", token.get_value(), token.get_start()),
             Error::UnprocessableCharacter(c, pos)
                 => write!(f, "Can't handle character {} in {}.", c, pos),
         }
@@ -391,13 +659,13 @@ impl Display for Error {
 
 #[derive(Debug, Clone)]
 pub struct Exception {
-    pub error: Error,
+    pub error: Box<Error>,
     trace: Vec<String>,
 }
 
 impl Exception {
     pub fn create(error: Error, method: &str) -> Self {
-        Self { error, trace: vec![String::from(method)] }
+        Self { error: Box::new(error), trace: vec![String::from(method)] }
     }
 
     pub fn relay(exception: Self, method: &str) -> Self {
@@ -418,3 +686,128 @@ impl Display for Exception {
         write!(f, "{}\n{}", self.error, &message)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::*;
+
+    #[test]
+    fn escapes_token_values_in_json() {
+        let tokens = Lexer::from("[[1,2],[3,4]] \\ [1,2]").lex().unwrap();
+        for token in &tokens {
+            let json: serde_json::Value = serde_json::from_str(&token.get_json(None)).unwrap();
+            assert_eq!(json["value"], token.get_value());
+        }
+        assert!(tokens.iter().any(|token| token.get_value() == "\\"));
+    }
+
+    #[test]
+    fn keeps_trivia_in_json() {
+        let text = " ( 1 +\t2 ) * 3 ";
+        let node = crate::parser::Parser::from(text).parse().unwrap();
+        let json: serde_json::Value = serde_json::from_str(&node.get_json(None)).unwrap();
+        let mut source = String::from("");
+        let mut stack = vec![&json];
+        while let Some(item) = stack.pop() {
+            match item["value"].as_array() {
+                Some(items) => stack.extend(items.iter().rev()),
+                None => {
+                    let text = |key: &str| -> String {
+                        item[key].as_array().into_iter().flatten().map(|token| token["value"].as_str().unwrap()).collect()
+                    };
+                    source += &(text("leading") + item["value"].as_str().unwrap() + &text("trailing"));
+                },
+            }
+        }
+        assert_eq!(source, text);
+    }
+
+    #[test]
+    fn walks_long_trees_without_recursion() {
+        let text = format!("1{}", " + 1".repeat(50_000));
+        let node = crate::parser::Parser::from(text.as_str()).parse().unwrap();
+        assert_eq!(node.get_text(), text);
+        assert_eq!(node.get_tokens().len(), 100_001);
+        let json = node.get_json(None);
+        assert_eq!(json.matches('{').count(), json.matches('}').count());
+        assert_eq!(json.matches(r#""type":"Add""#).count(), 50_000);
+        let short = crate::parser::Parser::from("1 + 1 + 1").parse().unwrap();
+        let json: serde_json::Value = serde_json::from_str(&short.get_json(None)).unwrap();
+        assert_eq!(json["type"], short.get_type());
+        let ast = node.clone().into_owned().get_ast();
+        assert_eq!(ast.get_code(), text.replace(' ', ""));
+        drop(node);
+        drop(ast);
+    }
+
+    #[test]
+    fn borrows_the_children_of_nodes() {
+        let node = crate::parser::Parser::from("1 + 2").parse().unwrap();
+        let sum = match node.children() {
+            [Right(sum)] => sum,
+            children => panic!("{:?}", children),
+        };
+        let kinds: Vec<String> = sum.children().iter().map(|item| for_both!(item, item => item.get_type())).collect();
+        assert_eq!(kinds, ["Number", "Operator", "Number"]);
+        assert_eq!(sum.children().len(), sum.get_value().len());
+    }
+
+    #[test]
+    fn rebuilds_the_source_from_the_tree() {
+        for text in [" ( 1 + 2 ) * 3 ", "f(x, y) = x ^ 2 +\ty", "[1, 2;\n 3, 4]", "x ? 1 : 2", "  -(-1)! % 3  ", "5 km to mi"] {
+            let node = crate::parser::Parser::from(text).parse().unwrap();
+            assert_eq!(node.get_text(), text);
+            assert_eq!(node.clone().into_owned().get_text(), text);
+        }
+    }
+
+    #[test]
+    fn derives_the_abstract_tree() {
+        let node = crate::parser::Parser::from("y = (1 + 2) * x ? 3 : 4").parse().unwrap();
+        let ast = node.get_ast();
+        assert_eq!(ast.get_code(), "y1+2*x34");
+        let kinds = |node: &Node| -> Vec<String> {
+            let mut kinds = vec![];
+            let mut stack = vec![node];
+            while let Some(node) = stack.pop() {
+                kinds.push(node.get_type());
+                stack.extend(node.children().iter().rev().filter_map(|item| item.as_ref().right()));
+            }
+            kinds
+        };
+        assert_eq!(kinds(&ast), ["Calcul", "Assign", "Conditional", "Multiply", "Add", "Constant"]);
+        assert_eq!(node.get_code(), "y=(1+2)*x?3:4");
+    }
+
+    #[test]
+    fn spans_nodes_over_their_tokens() {
+        let node = crate::parser::Parser::from("(1 + 2) * 3 ").parse().unwrap();
+        let product = node.children()[0].as_ref().right().unwrap();
+        assert_eq!(product.get_type(), "Multiply");
+        assert_eq!((product.get_start().cursor, product.get_next().cursor), (0, 11));
+        let group = product.children()[0].as_ref().right().unwrap();
+        assert_eq!(group.get_type(), "Group");
+        assert_eq!((group.get_start().cursor, group.get_next().cursor), (0, 7));
+        let ast = node.get_ast();
+        let sum = ast.children()[0].as_ref().right().unwrap().children()[0].as_ref().right().unwrap();
+        assert_eq!(sum.get_type(), "Add");
+        assert_eq!((sum.get_start().cursor, sum.get_next().cursor), (0, 7));
+    }
+
+    #[test]
+    fn counts_offsets_in_every_unit() {
+        let mut position = Position::default();
+        let mut previous = None;
+        for c in "é😀\r\nx".chars() {
+            position = position.advance(c, previous);
+            previous = Some(c);
+        }
+        assert_eq!(position, Position { cursor: 5, byte: 9, utf16: 6, line: 1, column: 1 });
+        assert_eq!(position.get_json(Offsets::default()), r#"{"cursor":5,"line":1,"column":1}"#);
+        let all = Offsets::parse("char, byte,utf16").unwrap();
+        assert_eq!(position.get_json(all), r#"{"cursor":5,"byte":9,"utf16":6,"line":1,"column":1}"#);
+        assert_eq!(Offsets::parse("utf16"), Some(Offsets { char: false, byte: false, utf16: true }));
+        assert_eq!(Offsets::parse("bytes"), None);
+    }
+}
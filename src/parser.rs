@@ -13,69 +13,104 @@ macro_rules! current_method {
     }}
 }
 
+/// The left-associative operators looser than sums, from the loosest to the tightest. `^` is
+/// only the exclusive or in programmer mode.
+const BINARY_OPERATORS: [&[(&str, &str)]; 7] = [
+    &[("||", "Or")],
+    &[("&&", "And")],
+    &[("==", "Equal"), ("!=", "NotEqual"), ("<", "Less"), ("<=", "LessEqual"), (">", "Greater"), (">=", "GreaterEqual")],
+    &[("|", "BitOr")],
+    &[("^", "BitXor")],
+    &[("&", "BitAnd")],
+    &[("<<", "ShiftLeft"), (">>", "ShiftRight")],
+];
+/// Level of the comparisons in `BINARY_OPERATORS`.
+const COMPARISON: usize = 2;
+
+/// Deepest nesting of parentheses, calls, prefix operators, powers and conditionals that is parsed.
+/// Chains of left-associative operators may be of any length, but deeper nesting would overflow the
+/// stack of the parser or of the evaluator walking the tree.
+const MAX_NESTING: usize = 32;
+
 /**************************************************************************************************/
 /*                                         LEXER STREAM                                           */
 /**************************************************************************************************/
 
-type LexerResult = Result<Token, Exception>;
+type LexerResult<'a> = Result<Token<'a>, Exception>;
 
-struct LexerStream {
-    buffer: Vec<Token>,
+struct LexerStream<'a> {
+    buffer: Vec<Token<'a>>,
     cursor: usize,
 }
 
-impl From<Lexer> for LexerStream {
-    fn from(mut lexer: Lexer) -> LexerStream {
-        LexerStream { buffer: lexer.read(), cursor: 0 }
+impl<'a> From<Lexer<'a>> for LexerStream<'a> {
+    fn from(mut lexer: Lexer<'a>) -> LexerStream<'a> {
+        LexerStream::from(lexer.read())
     }
 }
 
-impl From<String> for LexerStream {
-    fn from(content: String) -> Self {
+impl<'a> From<&'a str> for LexerStream<'a> {
+    fn from(content: &'a str) -> Self {
         LexerStream::from(Lexer::from(content))
     }
 }
 
-impl From<Vec<Token>> for LexerStream {
-    fn from(buffer: Vec<Token>) -> Self {
-        LexerStream { buffer, cursor: 0 }
+impl<'a> From<Vec<Token<'a>>> for LexerStream<'a> {
+    fn from(tokens: Vec<Token<'a>>) -> Self {
+        LexerStream { buffer: LexerStream::attach_trivia(tokens), cursor: 0 }
     }
 }
 
-impl LexerStream {
+impl<'a> LexerStream<'a> {
+    /// Moves the whitespace tokens into the trivia of their neighbours, so that the parser never
+    /// sees them and the text can still be rebuilt from the tree.
+    fn attach_trivia(tokens: Vec<Token<'a>>) -> Vec<Token<'a>> {
+        let mut buffer: Vec<Token> = vec![];
+        let mut trivia: Vec<Token> = vec![];
+        for mut token in tokens {
+            if token.is_whitespace(None) {
+                trivia.push(token);
+                continue;
+            }
+            token.get_trivia_mut().leading.append(&mut trivia);
+            buffer.push(token);
+        }
+        if let Some(token) = buffer.last_mut() {
+            token.get_trivia_mut().trailing.append(&mut trivia);
+        }
+        buffer
+    }
+
     pub fn eof(&self) -> bool {
         self.cursor == self.buffer.len()
     }
 
-    pub fn peek(&self, idx: isize) -> Option<Token> {
-        let value = idx.abs() as usize;
+    pub fn peek(&self, idx: isize) -> Option<Token<'a>> {
+        let value = idx.unsigned_abs();
         let pos = if idx < 0 { self.cursor - value } else { self.cursor + value };
-        let token = self.buffer.iter().nth(pos);
+        let token = self.buffer.get(pos);
 
-        if let Some(token) = token { Some(token.clone()) }
-        else { None }
+        token.cloned()
     }
 
-    pub fn is<F>(&self, predicate: F) -> bool where F: Fn(Token) -> bool {
+    pub fn is<F>(&self, predicate: F) -> bool where F: Fn(Token<'a>) -> bool {
         match self.peek(0) {
             None => false,
             Some(token) => predicate(token),
         }
     }
 
-    pub fn is_equal<F>(&self, value: &str, callback: F) -> bool where F: Fn(Token, char) -> bool {
+    pub fn is_equal<F>(&self, value: &str, callback: F) -> bool where F: Fn(Token<'a>, char) -> bool {
         for (idx, c) in value.chars().enumerate() {
-            if false == match self.peek(idx as isize) {
-                None => false,
-                Some(token) => callback(token, c),
-            } {
-                return false;
+            match self.peek(idx as isize) {
+                None => return false,
+                Some(token) => if !callback(token, c) { return false; },
             }
         }
-        return true;
+        true
     }
 
-    pub fn is_type_or_equal<F>(&self, value: Option<&str>, callback: F) -> bool where F: Fn(Token, Option<char>) -> bool {
+    pub fn is_type_or_equal<F>(&self, value: Option<&str>, callback: F) -> bool where F: Fn(Token<'a>, Option<char>) -> bool {
         if let Some(str) = value {
             self.is_equal(str, |token, char| callback(token, Some(char)))
         } else {
@@ -87,6 +122,11 @@ impl LexerStream {
         self.is(|token| token.is_identifier(value))
     }
 
+    pub fn is_number(&self, value: Option<&str>) -> bool {
+        self.is(|token| token.is_number(value))
+    }
+
+    #[allow(dead_code)]
     pub fn is_muted(&self) -> bool {
         self.is(|token| token.is_whitespace(None))
     }
@@ -95,6 +135,7 @@ impl LexerStream {
         self.is_type_or_equal(None, |token, _| token.is_operator(value))
     }
 
+    #[allow(dead_code)]
     pub fn is_one_of_punctuation(&self, value: Vec<char>) -> bool {
         self.is(|token| token.is_one_of_punctuation(value.clone()))
     }
@@ -103,7 +144,7 @@ impl LexerStream {
         self.is_type_or_equal(value, |token, value| token.is_punctuation(value))
     }
 
-    pub fn next(&mut self) -> LexerResult {
+    pub fn next(&mut self) -> LexerResult<'a> {
         match self.peek(0) {
             None => Err(Exception::create(Error::EOF, current_method!())),
             Some(token) => {
@@ -113,10 +154,10 @@ impl LexerStream {
         }
     }
 
-    pub fn read<F>(&mut self, mut callback: F) -> LexerResult where F: FnMut(Token) -> bool {
+    pub fn read<F>(&mut self, mut callback: F) -> LexerResult<'a> where F: FnMut(Token<'a>) -> bool {
         if let Some(token) = self.peek(0) {
             if !callback(token.clone()) {
-                Err(Exception::create(Error::UnexpectedToken(token), current_method!()))
+                Err(Exception::create(Error::UnexpectedToken(token.into_owned()), current_method!()))
             } else {
                 Ok(self.next().unwrap())
             }
@@ -125,19 +166,19 @@ impl LexerStream {
         }
     }
 
-    pub fn read_punctuation(&mut self, value: Option<&str>) -> LexerResult {
+    pub fn read_punctuation(&mut self, value: Option<&str>) -> LexerResult<'a> {
         if self.is_punctuation(value) {
             self.read(|_| true)
         } else {
             if let Some(token) = self.peek(0) {
-                Err(Exception::create(Error::UnexpectedToken(token), current_method!()))
+                Err(Exception::create(Error::UnexpectedToken(token.into_owned()), current_method!()))
             } else {
                 Err(Exception::create(Error::EOF, current_method!()))
             }
         }
     }
 
-    pub fn read_while<F>(&mut self, mut predicate: F) -> Vec<Token> where F: FnMut(Token) -> bool {
+    pub fn read_while<F>(&mut self, mut predicate: F) -> Vec<Token<'a>> where F: FnMut(Token<'a>) -> bool {
         let mut values: Vec<Token> = vec![];
         while !self.eof() {
             let token = self.peek(0).unwrap();
@@ -153,37 +194,245 @@ impl LexerStream {
 /*                                            PARSER                                              */
 /**************************************************************************************************/
 
-type ParserResult = Result<Node, Exception>;
+type ParserResult<'a> = Result<Node<'a>, Exception>;
 
-pub struct Parser {
-    stream: LexerStream,
+pub struct Parser<'a> {
+    stream: LexerStream<'a>,
+    xor_caret: bool,
+    depth: usize,
 }
 
-impl From<String> for Parser {
-    fn from(content: String) -> Self {
-        Parser { stream: LexerStream::from(content) }
+impl<'a> From<&'a str> for Parser<'a> {
+    fn from(content: &'a str) -> Self {
+        Parser { stream: LexerStream::from(content), xor_caret: false, depth: 0 }
     }
 }
 
-impl From<Vec<Token>> for Parser {
-    fn from(buffer: Vec<Token>) -> Self {
-        Parser { stream: LexerStream::from(buffer) }
+impl<'a> From<Vec<Token<'a>>> for Parser<'a> {
+    fn from(buffer: Vec<Token<'a>>) -> Self {
+        Parser { stream: LexerStream::from(buffer), xor_caret: false, depth: 0 }
     }
 }
 
-impl Parser {
+impl<'a> Parser<'a> {
+    /// In programmer mode `^` is the bitwise exclusive or, and only `**` is left for powers.
+    pub fn set_xor_caret(&mut self, xor_caret: bool) {
+        self.xor_caret = xor_caret;
+    }
+
     pub fn eof(&self) -> bool {
         self.stream.eof()
     }
 
-    pub fn parse(&mut self) -> ParserResult {
-        match self.parse_additive() {
+    pub fn parse(&mut self) -> ParserResult<'a> {
+        match self.parse_expression() {
+            Err(error) => Err(Exception::relay(error, current_method!())),
+            Ok(value) => match self.stream.peek(0) {
+                Some(token) => Err(Exception::create(Error::UnexpectedToken(token.into_owned()), current_method!())),
+                None => Ok(Node::create("Calcul", vec![value])),
+            }
+        }
+    }
+
+    /// Parses one level of nesting deeper, as the expression in parentheses.
+    fn nested<F>(&mut self, parse: F) -> Result<NodeItem<'a>, Exception>
+        where F: Fn(&mut Parser<'a>) -> Result<NodeItem<'a>, Exception> {
+        if self.depth == MAX_NESTING {
+            let token = self.stream.peek(0).or_else(|| self.stream.peek(-1));
+            let position = token.map(|token| token.get_start()).unwrap_or_default();
+            return Err(Exception::create(Error::NestingLimit(MAX_NESTING, position), current_method!()));
+        }
+        self.depth += 1;
+        let result = parse(self);
+        self.depth -= 1;
+        result
+    }
+
+    fn parse_expression(&mut self) -> Result<NodeItem<'a>, Exception> {
+        if self.is_definition() {
+            return match self.parse_definition() {
+                Err(error) => Err(Exception::relay(error, current_method!())),
+                Ok(node) => Ok(Right(node)),
+            };
+        }
+        if self.stream.is_identifier(None) && self.is_assignment(1) {
+            return match self.parse_assignment() {
+                Err(error) => Err(Exception::relay(error, current_method!())),
+                Ok(node) => Ok(Right(node)),
+            };
+        }
+
+        match self.parse_conversion() {
+            Err(error) => Err(Exception::relay(error, current_method!())),
+            Ok(value) => Ok(value),
+        }
+    }
+
+    fn is_assignment(&self, idx: isize) -> bool {
+        match self.stream.peek(idx) {
+            None => false,
+            Some(token) => token.is_operator(Some("=")),
+        }
+    }
+
+    /// Looks ahead for `name(param, ...) =`, which can't be told apart from a call before the `=`.
+    fn is_definition(&self) -> bool {
+        if !self.stream.is_identifier(None) {
+            return false;
+        }
+        match self.stream.peek(1) {
+            Some(token) if token.is_punctuation(Some('(')) => {},
+            _ => return false,
+        }
+
+        let mut idx = 2;
+        let mut expect_parameter = true;
+        while let Some(token) = self.stream.peek(idx) {
+            if token.is_punctuation(Some(')')) {
+                return (idx == 2 || !expect_parameter) && self.is_assignment(idx + 1);
+            } else if expect_parameter && token.is_identifier(None) {
+                expect_parameter = false;
+            } else if !expect_parameter && token.is_punctuation(Some(',')) {
+                expect_parameter = true;
+            } else {
+                return false;
+            }
+            idx += 1;
+        }
+        false
+    }
+
+    fn parse_definition(&mut self) -> ParserResult<'a> {
+        let mut value: NodeValue = vec![];
+        let tokens = self.stream.read_while(|token| !token.is_operator(Some("=")));
+        for token in tokens {
+            value.push(Left(token));
+        }
+        match self.stream.next() {
+            Err(error) => return Err(Exception::relay(error, current_method!())),
+            Ok(token) => value.push(Left(token)),
+        }
+
+        match self.nested(Parser::parse_expression) {
+            Err(error) => Err(Exception::relay(error, current_method!())),
+            Ok(body) => {
+                value.push(body);
+                Ok(Node::create("Define", value))
+            }
+        }
+    }
+
+    fn parse_assignment(&mut self) -> ParserResult<'a> {
+        let name = match self.stream.next() {
+            Err(error) => return Err(Exception::relay(error, current_method!())),
+            Ok(token) => token,
+        };
+        let operator = match self.stream.next() {
+            Err(error) => return Err(Exception::relay(error, current_method!())),
+            Ok(token) => token,
+        };
+
+        match self.nested(Parser::parse_expression) {
+            Err(error) => Err(Exception::relay(error, current_method!())),
+            Ok(value) => Ok(Node::create("Assign", vec![Left(name), Left(operator), value])),
+        }
+    }
+
+    /// Reads `value to unit` and `value in unit`, the unit being a product of unit names.
+    fn parse_conversion(&mut self) -> Result<NodeItem<'a>, Exception> {
+        let mut value = match self.parse_conditional() {
+            Err(error) => return Err(Exception::relay(error, current_method!())),
+            Ok(value) => value,
+        };
+        while self.is_conversion() {
+            let token = self.stream.next().unwrap();
+            match self.parse_multiplicative() {
+                Err(error) => return Err(Exception::relay(error, current_method!())),
+                Ok(unit) => value = Right(Node::create("Convert", vec![value, Left(token), unit])),
+            }
+        }
+        Ok(value)
+    }
+
+    fn is_conversion(&self) -> bool {
+        self.stream.is_identifier(Some("to")) || self.stream.is_identifier(Some("in"))
+    }
+
+    fn parse_conditional(&mut self) -> Result<NodeItem<'a>, Exception> {
+        let condition = match self.parse_binary(0) {
+            Err(error) => return Err(Exception::relay(error, current_method!())),
+            Ok(condition) => condition,
+        };
+        if !self.stream.is_operator(Some("?")) {
+            return Ok(condition);
+        }
+        let question = self.stream.next().unwrap();
+
+        let consequent = match self.nested(Parser::parse_conditional) {
+            Err(error) => return Err(Exception::relay(error, current_method!())),
+            Ok(consequent) => consequent,
+        };
+        let colon = match self.stream.read_punctuation(Some(":")) {
+            Err(error) => return Err(Exception::relay(error, current_method!())),
+            Ok(token) => token,
+        };
+        match self.nested(Parser::parse_conditional) {
+            Err(error) => Err(Exception::relay(error, current_method!())),
+            Ok(alternative) => {
+                let value = vec![condition, Left(question), consequent, Left(colon), alternative];
+                Ok(Right(Node::create("Conditional", value)))
+            },
+        }
+    }
+
+    /// Parses the operators looser than sums by precedence climbing: an operand takes a frame of the
+    /// stack for each operator above it rather than one for every level of `BINARY_OPERATORS`, and
+    /// a chain of operators of one level is read in a loop.
+    fn parse_binary(&mut self, level: usize) -> Result<NodeItem<'a>, Exception> {
+        let mut left = match self.parse_operand(level) {
+            Err(error) => return Err(Exception::relay(error, current_method!())),
+            Ok(left) => left,
+        };
+
+        while let Some((precedence, kind)) = self.find_binary(level) {
+            let token = self.stream.next().unwrap();
+            match self.parse_binary(precedence + 1) {
+                Err(error) => return Err(Exception::relay(error, current_method!())),
+                Ok(right) => left = Right(Node::create(kind, vec![left, Left(token), right])),
+            }
+        }
+        Ok(left)
+    }
+
+    /// Finds the operator at the cursor among the levels of `BINARY_OPERATORS` from the given one,
+    /// with its level and its node type.
+    fn find_binary(&self, level: usize) -> Option<(usize, &'static str)> {
+        let token = self.stream.peek(0)?;
+        BINARY_OPERATORS.iter().enumerate().skip(level)
+            // In programmer mode `^` is the bitwise exclusive or.
+            .filter(|(_, operators)| self.xor_caret || operators[0].1 != "BitXor")
+            .find_map(|(precedence, operators)| {
+                operators.iter().find(|(operator, _)| token.is_operator(Some(operator))).map(|(_, kind)| (precedence, *kind))
+            })
+    }
+
+    /// A `!` binds looser than comparisons, so that `!a == b` is `!(a == b)`.
+    fn parse_operand(&mut self, level: usize) -> Result<NodeItem<'a>, Exception> {
+        if level > COMPARISON || !self.stream.is_operator(Some("!")) {
+            return match self.parse_additive() {
+                Err(error) => Err(Exception::relay(error, current_method!())),
+                Ok(value) => Ok(value),
+            };
+        }
+
+        let token = self.stream.next().unwrap();
+        match self.nested(|parser| parser.parse_binary(COMPARISON)) {
             Err(error) => Err(Exception::relay(error, current_method!())),
-            Ok(value) => Ok(Node::create("Calcul", vec![value]))
+            Ok(operand) => Ok(Right(Node::create("Not", vec![Left(token), operand]))),
         }
     }
 
-    fn parse_additive(&mut self) -> Result<NodeItem, Exception> {
+    fn parse_additive(&mut self) -> Result<NodeItem<'a>, Exception> {
         match self.parse_multiplicative() {
             Err(error) => Err(Exception::relay(error, current_method!())),
             Ok(mut left) => {
@@ -196,11 +445,11 @@ impl Parser {
                                 Err(error) => return Err(Exception::relay(error, current_method!())),
                                 Ok(right) => {
                                     if token.is_operator(Some("+")) {
-                                        left = Right(Node::create("Add", vec![left, right]))
+                                        left = Right(Node::create("Add", vec![left, Left(token.clone()), right]))
                                     } else if token.is_operator(Some("-")) {
-                                        left = Right(Node::create("Subtract", vec![left, right]))
+                                        left = Right(Node::create("Subtract", vec![left, Left(token.clone()), right]))
                                     } else {
-                                        return Err(Exception::create(Error::UnexpectedToken(token), current_method!()));
+                                        return Err(Exception::create(Error::UnexpectedToken(token.into_owned()), current_method!()));
                                     }
                                 }
                             }
@@ -212,24 +461,37 @@ impl Parser {
         }
     }
 
-    fn parse_multiplicative(&mut self) -> Result<NodeItem, Exception> {
-        match self.parse_primary() {
+    fn parse_multiplicative(&mut self) -> Result<NodeItem<'a>, Exception> {
+        match self.parse_unary() {
             Err(error) => Err(Exception::relay(error, current_method!())),
             Ok(mut left) => {
                 while !self.stream.eof() {
-                    if !self.stream.is_operator(Some("*")) && !self.stream.is_operator(Some("/")) { break; }
+                    if !self.stream.is_operator(Some("*"))
+                        && !self.stream.is_operator(Some("/"))
+                        && !self.stream.is_operator(Some("%"))
+                        && !self.stream.is_operator(Some("\\"))
+                        && !self.stream.is_operator(Some(".*"))
+                        && !self.stream.is_operator(Some("./")) { break; }
                     match self.stream.next() {
                         Err(error) => return Err(Exception::relay(error, current_method!())),
                         Ok(token) => {
-                            match self.parse_primary() {
+                            match self.parse_unary() {
                                 Err(error) => return Err(Exception::relay(error, current_method!())),
                                 Ok(right) => {
                                     if token.is_operator(Some("*")) {
-                                        left = Right(Node::create("Multiply", vec![left, right]));
+                                        left = Right(Node::create("Multiply", vec![left, Left(token.clone()), right]));
                                     } else if token.is_operator(Some("/")) {
-                                        left = Right(Node::create("Divide", vec![left, right]));
+                                        left = Right(Node::create("Divide", vec![left, Left(token.clone()), right]));
+                                    } else if token.is_operator(Some("%")) {
+                                        left = Right(Node::create("Modulo", vec![left, Left(token.clone()), right]));
+                                    } else if token.is_operator(Some("\\")) {
+                                        left = Right(Node::create("Solve", vec![left, Left(token.clone()), right]));
+                                    } else if token.is_operator(Some(".*")) {
+                                        left = Right(Node::create("ElementMultiply", vec![left, Left(token.clone()), right]));
+                                    } else if token.is_operator(Some("./")) {
+                                        left = Right(Node::create("ElementDivide", vec![left, Left(token.clone()), right]));
                                     } else {
-                                        return Err(Exception::create(Error::UnexpectedToken(token.clone()), current_method!()));
+                                        return Err(Exception::create(Error::UnexpectedToken(token.clone().into_owned()), current_method!()));
                                     }
                                 }
                             }
@@ -242,26 +504,433 @@ impl Parser {
         }
     }
 
-    fn parse_primary(&mut self) -> Result<NodeItem, Exception> {
+    fn parse_unary(&mut self) -> Result<NodeItem<'a>, Exception> {
+        if !self.stream.is_operator(Some("+"))
+            && !self.stream.is_operator(Some("-"))
+            && !self.stream.is_operator(Some("~"))
+            && !self.stream.is_operator(Some("!")) {
+            return match self.parse_power() {
+                Err(error) => Err(Exception::relay(error, current_method!())),
+                Ok(value) => Ok(value),
+            };
+        }
+
+        match self.stream.next() {
+            Err(error) => Err(Exception::relay(error, current_method!())),
+            Ok(token) => {
+                match self.nested(Parser::parse_unary) {
+                    Err(error) => Err(Exception::relay(error, current_method!())),
+                    Ok(operand) => {
+                        if token.is_operator(Some("-")) {
+                            Ok(Right(Node::create("Negate", vec![Left(token), operand])))
+                        } else if token.is_operator(Some("~")) {
+                            Ok(Right(Node::create("BitNot", vec![Left(token), operand])))
+                        } else if token.is_operator(Some("!")) {
+                            Ok(Right(Node::create("Not", vec![Left(token), operand])))
+                        } else {
+                            Ok(Right(Node::create("Identity", vec![Left(token), operand])))
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn parse_power(&mut self) -> Result<NodeItem<'a>, Exception> {
+        match self.parse_postfix() {
+            Err(error) => Err(Exception::relay(error, current_method!())),
+            Ok(left) => {
+                let caret = !self.xor_caret && self.stream.is_operator(Some("^"));
+                if !caret && !self.stream.is_operator(Some("**")) {
+                    return Ok(left);
+                }
+                match self.stream.next() {
+                    Err(error) => Err(Exception::relay(error, current_method!())),
+                    // The exponent goes back through the unary level so that `2^-1` is accepted
+                    // and `2^3^2` nests to the right.
+                    Ok(token) => match self.nested(Parser::parse_unary) {
+                        Err(error) => Err(Exception::relay(error, current_method!())),
+                        Ok(right) => Ok(Right(Node::create("Power", vec![left, Left(token), right]))),
+                    }
+                }
+            }
+        }
+    }
+
+    fn parse_postfix(&mut self) -> Result<NodeItem<'a>, Exception> {
+        match self.parse_primary() {
+            Err(error) => Err(Exception::relay(error, current_method!())),
+            Ok(mut operand) => {
+                // An identifier after a number is its unit, as in `3 km`, and several of them make a
+                // compound quantity such as `3 h 25 min`.
+                if matches!(&operand, Left(token) if token.is_number(None)) && self.stream.is_identifier(None) && !self.is_conversion() {
+                    let name = self.stream.next().unwrap();
+                    operand = match self.parse_unit(name) {
+                        Err(error) => return Err(Exception::relay(error, current_method!())),
+                        Ok(unit) => Right(Node::create("Unit", vec![operand, unit])),
+                    };
+                    let mut parts = vec![];
+                    while self.is_unit_start() {
+                        let (number, name) = (self.stream.next().unwrap(), self.stream.next().unwrap());
+                        match self.parse_unit(name) {
+                            Err(error) => return Err(Exception::relay(error, current_method!())),
+                            Ok(unit) => parts.push(Right(Node::create("Unit", vec![Left(number), unit]))),
+                        }
+                    }
+                    if !parts.is_empty() {
+                        parts.insert(0, operand);
+                        operand = Right(Node::create("Compound", parts));
+                    }
+                }
+                loop {
+                    // `%` is a percent sign unless an operand follows it, in which case
+                    // `parse_multiplicative` reads it as a modulo. `!` after an operand is a
+                    // factorial, `parse_operand` reading the prefix one.
+                    let kind = if self.stream.is_operator(Some("%")) && !self.is_operand_start(1) {
+                        "Percent"
+                    } else if self.stream.is_operator(Some("!")) {
+                        "Factorial"
+                    } else {
+                        return Ok(operand);
+                    };
+                    match self.stream.next() {
+                        Err(error) => return Err(Exception::relay(error, current_method!())),
+                        Ok(token) => operand = Right(Node::create(kind, vec![operand, Left(token)])),
+                    }
+                }
+            }
+        }
+    }
+
+    /// Reads the power of a unit after its name, so that only the unit is raised in `3 km^2`.
+    fn parse_unit(&mut self, name: Token<'a>) -> Result<NodeItem<'a>, Exception> {
+        let caret = !self.xor_caret && self.stream.is_operator(Some("^"));
+        if !caret && !self.stream.is_operator(Some("**")) {
+            return Ok(Left(name));
+        }
+        let token = self.stream.next().unwrap();
+        match self.nested(Parser::parse_unary) {
+            Err(error) => Err(Exception::relay(error, current_method!())),
+            Ok(exponent) => {
+                let unit = Node::create("Constant", vec![Left(name)]);
+                Ok(Right(Node::create("Power", vec![Right(unit), Left(token), exponent])))
+            },
+        }
+    }
+
+    fn is_unit_start(&self) -> bool {
+        let is_unit = |token: Token| token.is_identifier(None) && !["to", "in"].contains(&token.get_value().as_str());
+        self.stream.is_number(None) && self.stream.peek(1).is_some_and(is_unit)
+    }
+
+    /// Tells whether an operand starts at the token, whatever the whitespace around it. `+` and `-`
+    /// are read as binary operators, so that `10% - 5` and `10%-5` keep their percent and a negative
+    /// divisor is written `7 % (-2)`, while the prefix operators `~` and `!` start an operand.
+    fn is_operand_start(&self, idx: isize) -> bool {
+        match self.stream.peek(idx) {
+            None => false,
+            Some(token) if token.is_operator(None) => {
+                ["~", "!"].contains(&token.get_value().as_str()) && self.is_operand_start(idx + 1)
+            },
+            Some(token) => {
+                token.is_number(None) || token.is_identifier(None) || token.is_punctuation(Some('(')) || token.is_punctuation(Some('['))
+            },
+        }
+    }
+
+    fn parse_primary(&mut self) -> Result<NodeItem<'a>, Exception> {
         match self.stream.next() {
             Err(error) => Err(Exception::relay(error, current_method!())),
             Ok(token) => {
                 if token.is_number(None) {
                     Ok(Left(token))
+                } else if token.is_identifier(None) {
+                    // A currency symbol may come before its amount, as in `$45`.
+                    if !token.get_value().starts_with(char::is_alphanumeric) && self.stream.is_number(None) {
+                        let amount = self.stream.next().unwrap();
+                        Ok(Right(Node::create("Unit", vec![Left(token), Left(amount)])))
+                    } else if self.stream.is_punctuation(Some("(")) {
+                        match self.parse_call(token) {
+                            Err(error) => Err(Exception::relay(error, current_method!())),
+                            Ok(node) => Ok(Right(node)),
+                        }
+                    } else {
+                        Ok(Right(Node::create("Constant", vec![Left(token)])))
+                    }
+                } else if token.is_punctuation(Some('[')) {
+                    match self.parse_list(token) {
+                        Err(error) => Err(Exception::relay(error, current_method!())),
+                        Ok(node) => Ok(Right(node)),
+                    }
                 } else if token.is_punctuation(Some('(')) {
-                    match self.parse_additive() {
+                    match self.nested(Parser::parse_expression) {
                         Err(error) => Err(Exception::relay(error, current_method!())),
                         Ok(node) => {
                             match self.stream.read_punctuation(Some(")")) {
                                 Err(error) => Err(Exception::relay(error, current_method!())),
-                                Ok(_) => Ok(node)
+                                Ok(close) => Ok(Right(Node::create("Group", vec![Left(token), node, Left(close)]))),
                             }
                         }
                     }
                 } else {
-                    Err(Exception::create(Error::UnexpectedToken(token.clone()), current_method!()))
+                    Err(Exception::create(Error::UnexpectedToken(token.clone().into_owned()), current_method!()))
                 }
             }
         }
     }
+
+    fn parse_call(&mut self, name: Token<'a>) -> ParserResult<'a> {
+        let mut value: NodeValue = vec![Left(name)];
+        match self.stream.read_punctuation(Some("(")) {
+            Err(error) => return Err(Exception::relay(error, current_method!())),
+            Ok(token) => value.push(Left(token)),
+        }
+
+        if self.stream.is_punctuation(Some(")")) {
+            value.push(Left(self.stream.next().unwrap()));
+            return Ok(Node::create("Call", value));
+        }
+
+        loop {
+            match self.nested(Parser::parse_expression) {
+                Err(error) => return Err(Exception::relay(error, current_method!())),
+                Ok(argument) => value.push(argument),
+            }
+
+            if self.stream.is_punctuation(Some(",")) {
+                value.push(Left(self.stream.next().unwrap()));
+                continue;
+            }
+
+            match self.stream.read_punctuation(Some(")")) {
+                Err(error) => return Err(Exception::relay(error, current_method!())),
+                Ok(token) => value.push(Left(token)),
+            }
+            return Ok(Node::create("Call", value));
+        }
+    }
+
+    /// Reads a vector as `[1, 2, 3]` and a matrix as `[[1, 2], [3, 4]]` or `[1, 2; 3, 4]`, keeping the
+    /// separators so that the evaluator can tell the rows apart.
+    fn parse_list(&mut self, open: Token<'a>) -> ParserResult<'a> {
+        let mut value: NodeValue = vec![Left(open)];
+        if self.stream.is_punctuation(Some("]")) {
+            value.push(Left(self.stream.next().unwrap()));
+            return Ok(Node::create("List", value));
+        }
+
+        loop {
+            match self.nested(Parser::parse_expression) {
+                Err(error) => return Err(Exception::relay(error, current_method!())),
+                Ok(element) => value.push(element),
+            }
+
+            if self.stream.is_punctuation(Some(",")) || self.stream.is_punctuation(Some(";")) {
+                value.push(Left(self.stream.next().unwrap()));
+                continue;
+            }
+
+            match self.stream.read_punctuation(Some("]")) {
+                Err(error) => return Err(Exception::relay(error, current_method!())),
+                Ok(token) => value.push(Left(token)),
+            }
+            return Ok(Node::create("List", value));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Gives the kind of the expression at the root of the tree.
+    fn parse(text: &str) -> String {
+        let node = Parser::from(text).parse().unwrap();
+        match node.children().first() {
+            Some(Right(node)) => node.get_type(),
+            Some(Left(token)) => token.get_type(),
+            None => String::from(""),
+        }
+    }
+
+    #[test]
+    fn reads_signed_operands_of_modulo() {
+        assert_eq!(parse("7 % (-2)"), "Modulo");
+        assert_eq!(parse("7 % ~2"), "Modulo");
+        assert_eq!(parse("7 %~2"), "Modulo");
+        assert_eq!(parse("7 % !x"), "Modulo");
+        assert_eq!(parse("7 % [1, 2]"), "Modulo");
+        assert_eq!(parse("200 + 10% - 5"), "Subtract");
+        assert_eq!(parse("10%"), "Percent");
+    }
+
+    #[test]
+    fn binds_not_looser_than_comparisons() {
+        assert_eq!(parse("!a == b"), "Not");
+        assert_eq!(parse("!a == b && c"), "And");
+        assert_eq!(parse("a < b || !c"), "Or");
+        assert_eq!(parse("a | b == c"), "Equal");
+        assert_eq!(parse("a << b | c"), "BitOr");
+    }
+
+    #[test]
+    fn limits_nesting_but_not_length() {
+        assert_eq!(parse(&format!("1{}", " + 1".repeat(50_000))), "Add");
+        assert_eq!(parse(&format!("2{}", " * 2".repeat(50_000))), "Multiply");
+        let depth = |count: usize| format!("{}1{}", "(".repeat(count), ")".repeat(count));
+        assert_eq!(parse(&depth(MAX_NESTING)), "Group");
+        let error = Parser::from(depth(MAX_NESTING + 1).as_str()).parse().unwrap_err();
+        assert!(matches!(*error.error, Error::NestingLimit(MAX_NESTING, _)));
+        let error = Parser::from(format!("{}1", "-".repeat(10_000)).as_str()).parse().unwrap_err();
+        assert!(matches!(*error.error, Error::NestingLimit(MAX_NESTING, _)));
+    }
+
+    #[test]
+    fn reads_unary_signs() {
+        assert_eq!(parse("-2"), "Negate");
+        assert_eq!(parse("+2"), "Identity");
+        assert_eq!(parse("--2"), "Negate");
+        assert_eq!(parse("2 - -3"), "Subtract");
+        assert_eq!(parse("-2^2"), "Negate");
+    }
+
+    #[test]
+    fn nests_powers_to_the_right() {
+        let node = Parser::from("2^3^2").parse().unwrap();
+        let power = match node.children() {
+            [Right(power)] => power,
+            children => panic!("{:?}", children),
+        };
+        assert_eq!(power.get_type(), "Power");
+        assert!(matches!(power.children(), [Left(_), Left(_), Right(right)] if right.get_type() == "Power"));
+        assert_eq!(parse("2 ** 3"), "Power");
+        assert_eq!(parse("2^-1"), "Power");
+    }
+
+    #[test]
+    fn reads_calls_and_their_arguments() {
+        let node = Parser::from("max(1, 2 + 3, f(4))").parse().unwrap();
+        let call = match node.children() {
+            [Right(call)] => call,
+            children => panic!("{:?}", children),
+        };
+        assert_eq!(call.get_type(), "Call");
+        let arguments = call.children().iter().filter(|item| !matches!(item, Left(token) if token.is_punctuation(None))).count();
+        assert_eq!(arguments, 4);
+        assert_eq!(parse("sqrt()"), "Call");
+        assert!(Parser::from("sqrt(1,").parse().is_err());
+    }
+
+    #[test]
+    fn reads_assignments() {
+        assert_eq!(parse("x = 1 + 2"), "Assign");
+        assert_eq!(parse("x == 1"), "Equal");
+        assert_eq!(parse("x = y = 2"), "Assign");
+        assert!(Parser::from("1 = 2").parse().is_err());
+    }
+
+    #[test]
+    fn reads_definitions() {
+        assert_eq!(parse("f(x, y) = x^2 + y"), "Define");
+        assert_eq!(parse("f() = 1"), "Define");
+        assert_eq!(parse("f(x, y)"), "Call");
+        assert!(Parser::from("f(1) = 2").parse().is_err());
+    }
+
+    #[test]
+    fn reads_conditionals() {
+        assert_eq!(parse("a ? b : c"), "Conditional");
+        assert_eq!(parse("a || b ? 1 : 2"), "Conditional");
+        assert_eq!(parse("a ? b : c ? d : e"), "Conditional");
+        assert!(Parser::from("a ? b").parse().is_err());
+    }
+
+    #[test]
+    fn reads_carets_as_exclusive_ors_in_programmer_mode() {
+        assert_eq!(parse("6 ^ 3"), "Power");
+        let mut parser = Parser::from("6 ^ 3");
+        parser.set_xor_caret(true);
+        let node = parser.parse().unwrap();
+        assert!(matches!(node.children(), [Right(node)] if node.get_type() == "BitXor"));
+        assert_eq!(parse("1 | 2 & 3 << 4"), "BitOr");
+    }
+
+    #[test]
+    fn reads_units_after_numbers() {
+        assert_eq!(parse("3 km"), "Unit");
+        assert_eq!(parse("3 h 25 min"), "Compound");
+        assert_eq!(parse("5 km to mi"), "Convert");
+        assert_eq!(parse("5 km in m"), "Convert");
+        assert_eq!(parse("3 km + 200 m"), "Add");
+    }
+
+    #[test]
+    fn reads_lists() {
+        assert_eq!(parse("[1, 2, 3]"), "List");
+        assert_eq!(parse("[[1, 2], [3, 4]]"), "List");
+        assert_eq!(parse("[1, 2; 3, 4]"), "List");
+        assert_eq!(parse("[]"), "List");
+        assert_eq!(parse("A \\ b"), "Solve");
+        assert_eq!(parse("a .* b"), "ElementMultiply");
+        assert!(Parser::from("[1, 2").parse().is_err());
+    }
+
+    #[test]
+    fn attaches_whitespace_to_the_following_token() {
+        let stream = LexerStream::from(" 1 +\t2 ");
+        let trivia: Vec<(String, String, String)> = stream.buffer.iter().map(|token| {
+            let text = |trivia: &Vec<Token>| trivia.iter().map(|token| token.get_value()).collect();
+            (text(&token.get_trivia().leading), token.get_value(), text(&token.get_trivia().trailing))
+        }).collect();
+        let expected = [(" ", "1", ""), (" ", "+", ""), ("\t", "2", " ")];
+        assert_eq!(trivia, expected.map(|(a, b, c)| (a.to_string(), b.to_string(), c.to_string())));
+        assert_eq!(parse(" \n1\n+\n2\n "), "Add");
+    }
+
+    #[test]
+    fn keeps_parentheses_in_groups() {
+        assert_eq!(parse("(1 + 2)"), "Group");
+        assert_eq!(parse("(1 + 2) * 3"), "Multiply");
+        let node = Parser::from("((1))").parse().unwrap();
+        assert_eq!(node.get_text(), "((1))");
+        assert_eq!(node.get_tokens().len(), 5);
+    }
+
+    #[test]
+    fn reads_long_chains_of_every_operator() {
+        let chain = |operator: &str| format!("1{}", format!(" {} 1", operator).repeat(100));
+        for (operator, kind) in [("&&", "And"), ("||", "Or"), ("==", "Equal"), ("|", "BitOr"), ("&", "BitAnd"), ("<<", "ShiftLeft")] {
+            assert_eq!(parse(&chain(operator)), kind);
+        }
+        assert_eq!(parse(&format!("3 km{}", " to m".repeat(100))), "Convert");
+        assert_eq!(parse(&format!("3{}", "!".repeat(100))), "Factorial");
+        assert_eq!(parse(&format!("50{}", "%".repeat(100))), "Percent");
+        let error = Parser::from(format!("{}1", "2^".repeat(MAX_NESTING + 1)).as_str()).parse().unwrap_err();
+        assert!(matches!(*error.error, Error::NestingLimit(MAX_NESTING, _)));
+    }
+
+    #[test]
+    fn reads_powers_of_units_inside_quantities() {
+        let node = Parser::from("3 km^2").parse().unwrap();
+        let quantity = node.children()[0].as_ref().right().unwrap();
+        assert_eq!(quantity.get_type(), "Unit");
+        assert_eq!(quantity.children()[1].as_ref().right().unwrap().get_type(), "Power");
+        assert_eq!(parse("5 s^-1 + 1 Hz"), "Add");
+        assert_eq!(parse("(3 km)^2"), "Power");
+        let mut parser = Parser::from("3 km^2");
+        parser.set_xor_caret(true);
+        assert_eq!(parser.parse().unwrap().children()[0].as_ref().right().unwrap().get_type(), "BitXor");
+    }
+
+    #[test]
+    fn reads_percent_and_modulo_whatever_the_spacing() {
+        for text in ["10 % 3", "10 %3", "10% 3", "10%3", "10 % x", "10 %(3)"] {
+            assert_eq!(parse(text), "Modulo", "{}", text);
+        }
+        for text in ["10 % - 3", "10 %-3", "10% -3", "10%-3", "10 % + 3", "10 %+3"] {
+            assert_eq!(parse(text), if text.contains('-') { "Subtract" } else { "Add" }, "{}", text);
+        }
+        assert_eq!(parse("10 % !"), "Factorial");
+        assert_eq!(parse("10 %"), "Percent");
+    }
 }
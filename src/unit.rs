@@ -0,0 +1,321 @@
+use std::f64::consts;
+use std::fmt::{Display, Formatter};
+
+/**************************************************************************************************/
+/*                                           DIMENSION                                            */
+/**************************************************************************************************/
+
+/// The SI unit measuring each base dimension, in the order of the exponents of `Dimension`.
+pub const BASE_UNITS: [&str; 7] = ["m", "kg", "s", "A", "K", "mol", "cd"];
+
+/// Exponents of the base dimensions, force being `[1, 1, -2, 0, 0, 0, 0]` for `m·kg/s^2`.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct Dimension(pub [i32; 7]);
+
+const NONE: Dimension = Dimension([0, 0, 0, 0, 0, 0, 0]);
+const LENGTH: Dimension = Dimension([1, 0, 0, 0, 0, 0, 0]);
+const MASS: Dimension = Dimension([0, 1, 0, 0, 0, 0, 0]);
+const TIME: Dimension = Dimension([0, 0, 1, 0, 0, 0, 0]);
+const CURRENT: Dimension = Dimension([0, 0, 0, 1, 0, 0, 0]);
+const TEMPERATURE: Dimension = Dimension([0, 0, 0, 0, 1, 0, 0]);
+const AMOUNT: Dimension = Dimension([0, 0, 0, 0, 0, 1, 0]);
+const LUMINOSITY: Dimension = Dimension([0, 0, 0, 0, 0, 0, 1]);
+const AREA: Dimension = Dimension([2, 0, 0, 0, 0, 0, 0]);
+const VOLUME: Dimension = Dimension([3, 0, 0, 0, 0, 0, 0]);
+const SPEED: Dimension = Dimension([1, 0, -1, 0, 0, 0, 0]);
+const FREQUENCY: Dimension = Dimension([0, 0, -1, 0, 0, 0, 0]);
+const FORCE: Dimension = Dimension([1, 1, -2, 0, 0, 0, 0]);
+const PRESSURE: Dimension = Dimension([-1, 1, -2, 0, 0, 0, 0]);
+const ENERGY: Dimension = Dimension([2, 1, -2, 0, 0, 0, 0]);
+const POWER: Dimension = Dimension([2, 1, -3, 0, 0, 0, 0]);
+const CHARGE: Dimension = Dimension([0, 0, 1, 1, 0, 0, 0]);
+const VOLTAGE: Dimension = Dimension([2, 1, -3, -1, 0, 0, 0]);
+const RESISTANCE: Dimension = Dimension([2, 1, -3, -2, 0, 0, 0]);
+const CAPACITANCE: Dimension = Dimension([-2, -1, 4, 2, 0, 0, 0]);
+const INDUCTION: Dimension = Dimension([0, 1, -2, -1, 0, 0, 0]);
+
+impl Dimension {
+    pub fn is_none(&self) -> bool {
+        *self == NONE
+    }
+
+    pub fn multiply(&self, other: &Dimension) -> Dimension {
+        Dimension(std::array::from_fn(|idx| self.0[idx] + other.0[idx]))
+    }
+
+    pub fn power(&self, exponent: i32) -> Dimension {
+        Dimension(self.0.map(|value| value * exponent))
+    }
+}
+
+/**************************************************************************************************/
+/*                                             UNITS                                              */
+/**************************************************************************************************/
+
+struct Definition {
+    /// The symbol first, which is the only name taking SI prefixes, then the other spellings.
+    names: &'static [&'static str],
+    /// Size of the unit in the SI units of its dimension.
+    scale: f64,
+    /// SI value of the zero of the unit, for the Celsius and Fahrenheit degrees.
+    offset: f64,
+    dimension: Dimension,
+    prefixes: bool,
+}
+
+const fn unit(names: &'static [&'static str], scale: f64, dimension: Dimension, prefixes: bool) -> Definition {
+    Definition { names, scale, offset: 0.0, dimension, prefixes }
+}
+
+static PREFIXES: [(&str, f64); 22] = [
+    ("Y", 1e24), ("Z", 1e21), ("E", 1e18), ("P", 1e15), ("T", 1e12), ("G", 1e9), ("M", 1e6),
+    ("k", 1e3), ("h", 1e2), ("da", 1e1), ("d", 1e-1), ("c", 1e-2), ("m", 1e-3), ("µ", 1e-6),
+    ("μ", 1e-6), ("u", 1e-6), ("n", 1e-9), ("p", 1e-12), ("f", 1e-15), ("a", 1e-18),
+    ("z", 1e-21), ("y", 1e-24),
+];
+
+static DEFINITIONS: [Definition; 58] = [
+    // Length
+    unit(&["m", "meter", "meters", "metre", "metres"], 1.0, LENGTH, true),
+    unit(&["inch", "inches"], 0.0254, LENGTH, false),
+    unit(&["ft", "foot", "feet"], 0.3048, LENGTH, false),
+    unit(&["yd", "yard", "yards"], 0.9144, LENGTH, false),
+    unit(&["mi", "mile", "miles"], 1609.344, LENGTH, false),
+    unit(&["nmi"], 1852.0, LENGTH, false),
+    unit(&["Å", "angstrom"], 1e-10, LENGTH, false),
+    unit(&["au"], 149_597_870_700.0, LENGTH, false),
+    unit(&["ly", "lightyear", "lightyears"], 9_460_730_472_580_800.0, LENGTH, false),
+    unit(&["pc", "parsec", "parsecs"], 3.085_677_581_491_367e16, LENGTH, true),
+    // Mass
+    unit(&["g", "gram", "grams"], 1e-3, MASS, true),
+    unit(&["t", "tonne", "tonnes"], 1e3, MASS, true),
+    unit(&["lb", "lbs", "pound", "pounds"], 0.453_592_37, MASS, false),
+    unit(&["oz", "ounce", "ounces"], 0.028_349_523_125, MASS, false),
+    unit(&["st", "stone"], 6.350_293_18, MASS, false),
+    // Time
+    unit(&["s", "sec", "second", "seconds"], 1.0, TIME, true),
+    unit(&["min", "minute", "minutes"], 60.0, TIME, false),
+    unit(&["h", "hr", "hour", "hours"], 3600.0, TIME, false),
+    unit(&["d", "day", "days"], 86400.0, TIME, false),
+    unit(&["wk", "week", "weeks"], 604_800.0, TIME, false),
+    unit(&["yr", "year", "years"], 31_557_600.0, TIME, false),
+    // Other base dimensions
+    unit(&["A", "ampere", "amperes", "amp", "amps"], 1.0, CURRENT, true),
+    unit(&["K", "kelvin"], 1.0, TEMPERATURE, true),
+    Definition { names: &["°C", "degC", "celsius"], scale: 1.0, offset: 273.15, dimension: TEMPERATURE, prefixes: false },
+    Definition { names: &["°F", "degF", "fahrenheit"], scale: 5.0 / 9.0, offset: 459.67 * 5.0 / 9.0, dimension: TEMPERATURE, prefixes: false },
+    unit(&["mol", "mole", "moles"], 1.0, AMOUNT, true),
+    unit(&["cd", "candela"], 1.0, LUMINOSITY, true),
+    // Area and volume
+    unit(&["ha", "hectare", "hectares"], 1e4, AREA, false),
+    unit(&["acre", "acres"], 4_046.856_422_4, AREA, false),
+    unit(&["L", "liter", "liters", "litre", "litres"], 1e-3, VOLUME, true),
+    unit(&["l"], 1e-3, VOLUME, true),
+    unit(&["gal", "gallon", "gallons"], 3.785_411_784e-3, VOLUME, false),
+    unit(&["qt", "quart", "quarts"], 9.463_529_46e-4, VOLUME, false),
+    unit(&["pt", "pint", "pints"], 4.731_764_73e-4, VOLUME, false),
+    unit(&["cup", "cups"], 2.365_882_365e-4, VOLUME, false),
+    unit(&["floz"], 2.957_352_956_25e-5, VOLUME, false),
+    // Speed
+    unit(&["mph"], 0.447_04, SPEED, false),
+    unit(&["kph"], 1.0 / 3.6, SPEED, false),
+    unit(&["kn", "knot", "knots"], 1852.0 / 3600.0, SPEED, false),
+    // Derived SI units
+    unit(&["Hz", "hertz"], 1.0, FREQUENCY, true),
+    unit(&["N", "newton", "newtons"], 1.0, FORCE, true),
+    unit(&["Pa", "pascal", "pascals"], 1.0, PRESSURE, true),
+    unit(&["J", "joule", "joules"], 1.0, ENERGY, true),
+    unit(&["W", "watt", "watts"], 1.0, POWER, true),
+    unit(&["C", "coulomb", "coulombs"], 1.0, CHARGE, true),
+    unit(&["V", "volt", "volts"], 1.0, VOLTAGE, true),
+    unit(&["Ω", "ohm", "ohms"], 1.0, RESISTANCE, true),
+    unit(&["F", "farad", "farads"], 1.0, CAPACITANCE, true),
+    unit(&["T", "tesla"], 1.0, INDUCTION, true),
+    // Other energy and pressure units
+    unit(&["Wh"], 3600.0, ENERGY, true),
+    unit(&["eV", "electronvolt", "electronvolts"], 1.602_176_634e-19, ENERGY, true),
+    unit(&["cal", "calorie", "calories"], 4.184, ENERGY, true),
+    unit(&["bar"], 1e5, PRESSURE, true),
+    unit(&["atm"], 101_325.0, PRESSURE, false),
+    unit(&["psi"], 6_894.757_293_168, PRESSURE, false),
+    unit(&["mmHg"], 133.322_387_415, PRESSURE, false),
+    // Angles are dimensionless, so that `sin(30 deg)` is a plain call.
+    unit(&["rad", "radian", "radians"], 1.0, NONE, false),
+    unit(&["deg", "degree", "degrees", "°"], consts::PI / 180.0, NONE, false),
+];
+
+/// Named units replacing a product of several units with their dimension, as `N` for `kg·m/s^2`.
+static DERIVED_UNITS: [&str; 7] = ["N", "Pa", "J", "W", "C", "V", "Ω"];
+
+/// A product of named units raised to powers, with its size in SI units.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Unit {
+    /// The units as written, `km/h` being `[("km", 1), ("h", -1)]`.
+    pub terms: Vec<(String, i32)>,
+    pub scale: f64,
+    pub offset: f64,
+    pub dimension: Dimension,
+}
+
+impl Unit {
+    /// Reads a unit symbol or name, possibly with an SI prefix as in `km` or `µs`.
+    pub fn parse(name: &str) -> Option<Unit> {
+        if let Some(definition) = DEFINITIONS.iter().find(|definition| definition.names.contains(&name)) {
+            return Some(Unit::create(name, definition.scale, definition.offset, definition.dimension));
+        }
+        PREFIXES.iter().find_map(|(prefix, factor)| {
+            let symbol = name.strip_prefix(prefix)?;
+            DEFINITIONS.iter()
+                .find(|definition| definition.prefixes && definition.names[0] == symbol)
+                .map(|definition| Unit::create(name, definition.scale * factor, definition.offset, definition.dimension))
+        })
+    }
+
+    fn create(name: &str, scale: f64, offset: f64, dimension: Dimension) -> Unit {
+        Unit { terms: vec![(String::from(name), 1)], scale, offset, dimension }
+    }
+
+    /// Gives the product of the SI base units with the given dimension, `m/s` for a speed.
+    pub fn get_base(dimension: Dimension) -> Unit {
+        let terms = BASE_UNITS.iter()
+            .zip(dimension.0)
+            .filter(|(_, power)| *power != 0)
+            .map(|(name, power)| (String::from(*name), power))
+            .collect();
+        Unit { terms, scale: 1.0, offset: 0.0, dimension }
+    }
+
+    /// Names the unit in error messages, a number without unit being `1`.
+    pub fn get_name(&self) -> String {
+        match self.terms.is_empty() {
+            true => String::from("1"),
+            false => self.to_string(),
+        }
+    }
+
+    pub fn to_si(&self, magnitude: f64) -> f64 {
+        magnitude * self.scale + self.offset
+    }
+
+    pub fn from_si(&self, value: f64) -> f64 {
+        (value - self.offset) / self.scale
+    }
+
+    /// Converts a difference between two values, which doesn't depend on where the zero of the
+    /// unit lies, as the `10 K` added in `20 °C + 10 K`.
+    pub fn to_si_difference(&self, magnitude: f64) -> f64 {
+        magnitude * self.scale
+    }
+
+    pub fn from_si_difference(&self, value: f64) -> f64 {
+        value / self.scale
+    }
+
+    /// Multiplies the units, merging the terms with the same name. Offsets are dropped, a product
+    /// of degrees Celsius being measured from the absolute zero.
+    pub fn multiply(&self, other: &Unit) -> Unit {
+        let mut terms = self.terms.clone();
+        for (name, power) in &other.terms {
+            match terms.iter_mut().find(|(term, _)| term == name) {
+                Some((_, existing)) => *existing += power,
+                None => terms.push((name.clone(), *power)),
+            }
+        }
+        terms.retain(|(_, power)| *power != 0);
+        Unit { terms, scale: self.scale * other.scale, offset: 0.0, dimension: self.dimension.multiply(&other.dimension) }
+    }
+
+    pub fn power(&self, exponent: i32) -> Unit {
+        Unit {
+            terms: self.terms.iter().map(|(name, power)| (name.clone(), power * exponent)).collect(),
+            scale: self.scale.powi(exponent),
+            offset: 0.0,
+            dimension: self.dimension.power(exponent),
+        }
+    }
+
+    /// Rewrites the result of a product, preferring a named SI unit such as `N`, then the SI base
+    /// units when several terms measure the same dimension, as in `mph·h`.
+    pub fn simplify(&self) -> Unit {
+        if self.terms.len() > 1 {
+            let derived = DERIVED_UNITS.iter()
+                .filter_map(|name| Unit::parse(name))
+                .find(|unit| unit.dimension == self.dimension);
+            if let Some(unit) = derived {
+                return unit;
+            }
+        }
+        let dimensions = self.dimension.0.iter().filter(|power| **power != 0).count();
+        if self.terms.len() > dimensions {
+            return Unit::get_base(self.dimension);
+        }
+        self.clone()
+    }
+}
+
+impl Default for Unit {
+    /// The unit of plain numbers, without any term.
+    fn default() -> Self {
+        Unit::get_base(NONE)
+    }
+}
+
+impl Display for Unit {
+    /// Writes the terms with a positive power, then the others after a `/`, as in `m·kg/s^2`.
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let write = |terms: Vec<(&String, i32)>| {
+            terms.iter()
+                .map(|(name, power)| if *power == 1 { name.to_string() } else { format!("{}^{}", name, power) })
+                .collect::<Vec<String>>()
+                .join("·")
+        };
+        let numerator: Vec<(&String, i32)> = self.terms.iter().filter(|(_, power)| *power > 0).map(|(name, power)| (name, *power)).collect();
+        let denominator: Vec<(&String, i32)> = self.terms.iter().filter(|(_, power)| *power < 0).map(|(name, power)| (name, -power)).collect();
+        match (numerator.is_empty(), denominator.is_empty()) {
+            (_, true) => write!(f, "{}", write(numerator)),
+            // Without numerator, `s^-1` reads better than `1/s` after a magnitude.
+            (true, false) => write!(f, "{}", write(self.terms.iter().map(|(name, power)| (name, *power)).collect())),
+            (false, false) => write!(f, "{}/{}", write(numerator), write(denominator)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_units_with_prefixes() {
+        let km = Unit::parse("km").unwrap();
+        assert_eq!((km.scale, km.dimension), (1000.0, LENGTH));
+        assert_eq!(Unit::parse("µs").unwrap().scale, 1e-6);
+        assert_eq!(Unit::parse("miles").unwrap().dimension, LENGTH);
+        // Only symbols take prefixes.
+        assert!(Unit::parse("kmeter").is_none());
+        assert!(Unit::parse("parsec_").is_none());
+        let celsius = Unit::parse("°C").unwrap();
+        assert!((celsius.to_si(100.0) - 373.15).abs() < 1e-9);
+        assert!((celsius.from_si(273.15)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn converts_differences_without_offsets() {
+        let (celsius, fahrenheit) = (Unit::parse("°C").unwrap(), Unit::parse("°F").unwrap());
+        assert_eq!(celsius.to_si_difference(10.0), 10.0);
+        assert!((fahrenheit.from_si_difference(celsius.to_si_difference(10.0)) - 18.0).abs() < 1e-9);
+        assert!((fahrenheit.from_si(celsius.to_si(10.0)) - 50.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn multiplies_units_into_named_ones() {
+        let acceleration = Unit::parse("m").unwrap().multiply(&Unit::parse("s").unwrap().power(-2));
+        assert_eq!(acceleration.to_string(), "m/s^2");
+        let force = acceleration.multiply(&Unit::parse("kg").unwrap());
+        assert_eq!(force.dimension, FORCE);
+        assert_eq!(force.simplify().to_string(), "N");
+        let distance = Unit::parse("mph").unwrap().multiply(&Unit::parse("h").unwrap());
+        assert_eq!(distance.simplify().to_string(), "m");
+        assert_eq!(Unit::parse("Hz").unwrap().multiply(&Unit::parse("s").unwrap()).simplify().get_name(), "1");
+        assert_eq!(Unit::get_base(FREQUENCY).to_string(), "s^-1");
+    }
+}
@@ -0,0 +1,397 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use num_bigint::BigInt;
+use num_complex::Complex64;
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime, TimeDelta};
+use crate::calendar::*;
+use crate::currency::*;
+use crate::decimal::*;
+use crate::matrix::*;
+use crate::unit::*;
+use num_integer::Integer;
+use num_rational::BigRational;
+use num_traits::{One, Signed, Zero};
+
+/// Decimal places written before giving up on finding the period of an expansion.
+const MAX_DECIMALS: usize = 256;
+/// Significant digits kept by the scientific form of a large integer.
+const SCIENTIFIC_DIGITS: usize = 16;
+/// Largest power of ten expanded when reading a number exactly, as in `1e100000`.
+const MAX_EXPONENT: u64 = 100_000;
+
+/// Writes a string of JSON, escaping quotes, backslashes and control characters.
+fn quote(text: &str) -> String {
+    serde_json::Value::from(text).to_string()
+}
+
+/**************************************************************************************************/
+/*                                          INTEGER TYPE                                          */
+/**************************************************************************************************/
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct IntegerType {
+    pub bits: u32,
+    pub signed: bool,
+}
+
+impl IntegerType {
+    /// Reads a type name such as `u8` or `i128`.
+    pub fn parse(name: &str) -> Option<IntegerType> {
+        let signed = match name.chars().next() {
+            Some('i') => true,
+            Some('u') => false,
+            _ => return None,
+        };
+        match name[1..].parse::<u32>() {
+            Ok(bits) if [8, 16, 32, 64, 128].contains(&bits) => Some(IntegerType { bits, signed }),
+            _ => None,
+        }
+    }
+
+    /// Splits the integer suffix from the text of a `Token::Number`, as in `255u8`.
+    pub fn split_suffix(text: &str) -> (&str, Option<IntegerType>) {
+        match text.rfind(['i', 'u']) {
+            Some(idx) => match IntegerType::parse(&text[idx..]) {
+                Some(integer_type) => (&text[..idx], Some(integer_type)),
+                None => (text, None),
+            },
+            None => (text, None),
+        }
+    }
+
+    pub fn min(&self) -> BigInt {
+        if self.signed { -(BigInt::one() << (self.bits - 1)) } else { BigInt::from(0) }
+    }
+
+    pub fn max(&self) -> BigInt {
+        if self.signed { (BigInt::one() << (self.bits - 1)) - 1 } else { (BigInt::one() << self.bits) - 1 }
+    }
+
+    pub fn contains(&self, value: &BigInt) -> bool {
+        self.min() <= *value && *value <= self.max()
+    }
+
+    /// Keeps the low `bits` bits of `value`, read back in two's complement when signed.
+    pub fn wrap(&self, value: &BigInt) -> BigInt {
+        let modulus = BigInt::one() << self.bits;
+        let value = ((value % &modulus) + &modulus) % &modulus;
+        if self.signed && value > self.max() { value - modulus } else { value }
+    }
+
+    pub fn saturate(&self, value: &BigInt) -> BigInt {
+        value.clone().clamp(self.min(), self.max())
+    }
+}
+
+impl Display for IntegerType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}{}", if self.signed { "i" } else { "u" }, self.bits)
+    }
+}
+
+/**************************************************************************************************/
+/*                                             VALUE                                              */
+/**************************************************************************************************/
+
+/// How complex values are written, `3+4i` or `5∠0.9272952180016122`.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub enum Format {
+    #[default]
+    Rectangular,
+    Polar,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Boolean(bool),
+    Complex(Complex64),
+    Date(NaiveDate),
+    DateTime(NaiveDateTime),
+    Decimal(Decimal),
+    Duration(TimeDelta),
+    Fixed(BigInt, IntegerType),
+    Function(String, Vec<String>),
+    Integer(BigInt),
+    Matrix(Matrix),
+    Money(Money),
+    Number(f64),
+    Quantity(f64, Unit),
+    Rational(BigRational),
+    Time(NaiveTime),
+    Vector(Vec<f64>),
+}
+
+impl Value {
+    pub fn get_type(&self) -> String {
+        match self {
+            Value::Boolean(_) => String::from("Boolean"),
+            Value::Complex(_) => String::from("Complex"),
+            Value::Date(_) => String::from("Date"),
+            Value::DateTime(_) => String::from("DateTime"),
+            Value::Decimal(_) => String::from("Decimal"),
+            Value::Duration(_) => String::from("Duration"),
+            Value::Fixed(_, integer_type) => integer_type.to_string(),
+            Value::Function(_, _) => String::from("Function"),
+            Value::Integer(_) => String::from("Integer"),
+            Value::Matrix(_) => String::from("Matrix"),
+            Value::Money(_) => String::from("Money"),
+            Value::Number(_) => String::from("Number"),
+            Value::Quantity(_, _) => String::from("Quantity"),
+            Value::Rational(_) => String::from("Rational"),
+            Value::Time(_) => String::from("Time"),
+            Value::Vector(_) => String::from("Vector"),
+        }
+    }
+
+    /// Writes the shape of a vector as its length and of a matrix as `2x3`, other values being
+    /// scalars.
+    pub fn get_shape(&self) -> String {
+        match self {
+            Value::Matrix(matrix) => matrix.get_shape(),
+            Value::Vector(vector) => vector.len().to_string(),
+            _ => String::from("scalar"),
+        }
+    }
+
+    /// Reads the text of an integer `Token::Number`: decimal digits, or a `0x`, `0o` or `0b` literal.
+    pub fn parse_integer(text: &str) -> Option<BigInt> {
+        let (digits, radix) = match text.get(..2).map(|prefix| prefix.to_ascii_lowercase()).as_deref() {
+            Some("0x") => (&text[2..], 16),
+            Some("0o") => (&text[2..], 8),
+            Some("0b") => (&text[2..], 2),
+            _ => (text, 10),
+        };
+        if digits.is_empty() || !digits.chars().all(|c| c == '_' || c.is_digit(radix)) {
+            return None;
+        }
+        BigInt::parse_bytes(digits.replace('_', "").as_bytes(), radix)
+    }
+
+    /// Reads the text of a `Token::Number` exactly, `0.1` giving `1/10`, and None for exponents beyond
+    /// `MAX_EXPONENT`.
+    pub fn parse_rational(text: &str) -> Option<BigRational> {
+        if let Some(integer) = Value::parse_integer(text) {
+            return Some(BigRational::from_integer(integer));
+        }
+        let (mantissa, exponent) = match text.find(['e', 'E']) {
+            Some(idx) => (&text[..idx], text[idx + 1..].parse::<i64>().ok()?),
+            None => (text, 0),
+        };
+        let (whole, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+        let digits = format!("{}{}", whole, fraction).replace('_', "");
+        if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        let numerator = BigInt::parse_bytes(digits.as_bytes(), 10)?;
+        let scale = exponent - fraction.replace('_', "").len() as i64;
+        if scale.unsigned_abs() > MAX_EXPONENT {
+            return None;
+        }
+        let power = num_traits::pow(BigInt::from(10), scale.unsigned_abs() as usize);
+        if scale < 0 {
+            Some(BigRational::new(numerator, power))
+        } else {
+            Some(BigRational::from_integer(numerator * power))
+        }
+    }
+
+    /// Writes the decimal expansion of a rational, its repeating digits in parentheses as in `0.1(6)`.
+    pub fn get_decimal(value: &BigRational) -> String {
+        let sign = if value.is_negative() { "-" } else { "" };
+        let denominator = value.denom();
+        let (whole, mut remainder) = value.numer().abs().div_rem(denominator);
+        let mut digits = String::new();
+        let mut remainders = HashMap::new();
+        while !remainder.is_zero() {
+            if let Some(&start) = remainders.get(&remainder) {
+                digits.insert(start, '(');
+                digits.push(')');
+                break;
+            }
+            if digits.len() == MAX_DECIMALS {
+                digits.push_str("...");
+                break;
+            }
+            remainders.insert(remainder.clone(), digits.len());
+            let (digit, rest) = (remainder * BigInt::from(10)).div_rem(denominator);
+            digits.push_str(&digit.to_string());
+            remainder = rest;
+        }
+        if digits.is_empty() {
+            format!("{}{}", sign, whole)
+        } else {
+            format!("{}{}.{}", sign, whole, digits)
+        }
+    }
+
+    /// Writes a large integer in scientific notation, truncated to a few significant digits.
+    pub fn get_scientific(value: &BigInt) -> String {
+        let sign = if value.is_negative() { "-" } else { "" };
+        let digits = value.abs().to_string();
+        let (first, rest) = digits.split_at(1);
+        let rest = rest[..rest.len().min(SCIENTIFIC_DIGITS - 1)].trim_end_matches('0');
+        match rest {
+            "" => format!("{}{}e{}", sign, first, digits.len() - 1),
+            rest => format!("{}{}.{}e{}", sign, first, rest, digits.len() - 1),
+        }
+    }
+
+    /// Gives a real number for complex values without imaginary part.
+    pub fn from_complex(value: Complex64) -> Value {
+        if value.im == 0.0 { Value::Number(value.re) } else { Value::Complex(value) }
+    }
+
+    pub fn format(&self, format: Format) -> String {
+        match (self, format) {
+            (Value::Complex(value), Format::Polar) => {
+                format!("{}∠{}", Value::Number(value.norm()), Value::Number(value.arg()))
+            },
+            _ => self.to_string(),
+        }
+    }
+
+    pub fn get_json(&self, format: Format) -> String {
+        let value = quote(&self.format(format));
+        match self {
+            // Like `Value::Number`, integers of 22 digits or more also get a scientific form.
+            Value::Integer(integer) if integer.abs().to_string().len() > 21 => format!(
+                r#"{{"type":"{}","value":{},"scientific":{}}}"#,
+                self.get_type(), value, quote(&Value::get_scientific(integer)),
+            ),
+            Value::Rational(rational) => format!(
+                r#"{{"type":"{}","value":{},"decimal":{}}}"#,
+                self.get_type(), value, quote(&Value::get_decimal(rational)),
+            ),
+            Value::Duration(duration) => format!(
+                r#"{{"type":"{}","value":{},"seconds":{}}}"#,
+                self.get_type(), value, quote(&Value::Number(get_seconds(duration)).to_string()),
+            ),
+            Value::Money(money) => {
+                let timestamp = match &money.timestamp {
+                    None => String::new(),
+                    Some(timestamp) => format!(r#","timestamp":{}"#, quote(timestamp)),
+                };
+                format!(
+                    r#"{{"type":"{}","value":{},"amount":{},"currency":{}{}}}"#,
+                    self.get_type(), value, quote(&money.get_amount()), quote(money.currency.code), timestamp,
+                )
+            },
+            Value::Quantity(magnitude, unit) => format!(
+                r#"{{"type":"{}","value":{},"magnitude":{},"unit":{}}}"#,
+                self.get_type(), value, quote(&Value::Number(*magnitude).to_string()), quote(&unit.to_string()),
+            ),
+            Value::Matrix(_) | Value::Vector(_) => format!(
+                r#"{{"type":"{}","value":{},"shape":{}}}"#,
+                self.get_type(), value, quote(&self.get_shape()),
+            ),
+            _ => format!(r#"{{"type":"{}","value":{}}}"#, self.get_type(), value),
+        }
+    }
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Boolean(value) => write!(f, "{}", value),
+            Value::Complex(value) => {
+                let imaginary = match value.im.abs() {
+                    1.0 => String::from("i"),
+                    magnitude => format!("{}i", Value::Number(magnitude)),
+                };
+                let negative = value.im.is_sign_negative();
+                match value.re {
+                    0.0 => write!(f, "{}{}", if negative { "-" } else { "" }, imaginary),
+                    re => write!(f, "{}{}{}", Value::Number(re), if negative { "-" } else { "+" }, imaginary),
+                }
+            },
+            Value::Date(value) => write!(f, "{}", value.format("%Y-%m-%d")),
+            Value::DateTime(value) => write!(f, "{}T{}", value.format("%Y-%m-%d"), format_time(&value.time())),
+            Value::Decimal(value) => write!(f, "{}", value),
+            Value::Duration(value) => write!(f, "{}", format_duration(value)),
+            Value::Fixed(value, _) => write!(f, "{}", value),
+            Value::Function(name, parameters) => write!(f, "{}({})", name, parameters.join(", ")),
+            Value::Integer(value) => write!(f, "{}", value),
+            Value::Matrix(value) => write!(f, "{}", value),
+            Value::Money(value) => write!(f, "{}", value),
+            Value::Number(value) => {
+                let magnitude = value.abs();
+                if magnitude != 0.0 && magnitude.is_finite() && !(1e-7..1e21).contains(&magnitude) {
+                    write!(f, "{:e}", value)
+                } else {
+                    write!(f, "{}", value)
+                }
+            },
+            Value::Quantity(magnitude, unit) => write!(f, "{} {}", Value::Number(*magnitude), unit),
+            Value::Rational(value) => write!(f, "{}", value),
+            Value::Time(value) => write!(f, "{}", format_time(value)),
+            Value::Vector(value) => write!(f, "{}", format_vector(value)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bounds_fixed_width_integers() {
+        let u8 = IntegerType::parse("u8").unwrap();
+        let i8 = IntegerType::parse("i8").unwrap();
+        assert!(IntegerType::parse("u7").is_none() && IntegerType::parse("x8").is_none());
+        assert_eq!((i8.min(), i8.max()), (BigInt::from(-128), BigInt::from(127)));
+        assert_eq!(u8.wrap(&BigInt::from(256)), BigInt::from(0));
+        assert_eq!(u8.wrap(&BigInt::from(-1)), BigInt::from(255));
+        assert_eq!(i8.wrap(&BigInt::from(128)), BigInt::from(-128));
+        assert_eq!(i8.saturate(&BigInt::from(-300)), BigInt::from(-128));
+        assert_eq!(IntegerType::split_suffix("255u8"), ("255", Some(u8)));
+        assert_eq!(IntegerType::split_suffix("0xfu"), ("0xfu", None));
+        assert_eq!(i8.to_string(), "i8");
+    }
+
+    #[test]
+    fn reads_and_expands_rationals_exactly() {
+        let rational = |numerator: i64, denominator: i64| BigRational::new(BigInt::from(numerator), BigInt::from(denominator));
+        assert_eq!(Value::parse_rational("0.1"), Some(rational(1, 10)));
+        assert_eq!(Value::parse_rational("1.5e-3"), Some(rational(3, 2000)));
+        assert_eq!(Value::parse_rational("2E3"), Some(rational(2000, 1)));
+        assert_eq!(Value::parse_rational("0x10"), Some(rational(16, 1)));
+        assert_eq!(Value::parse_rational("1.2.3"), None);
+        assert_eq!(Value::get_decimal(&rational(1, 6)), "0.1(6)");
+        assert_eq!(Value::get_decimal(&rational(-1, 3)), "-0.(3)");
+        assert_eq!(Value::get_decimal(&rational(1, 4)), "0.25");
+        assert_eq!(Value::get_decimal(&rational(3, 1)), "3");
+        let json = Value::Rational(rational(1, 6)).get_json(Format::default());
+        assert_eq!(json, r#"{"type":"Rational","value":"1/6","decimal":"0.1(6)"}"#);
+    }
+
+    #[test]
+    fn writes_large_integers_in_scientific_notation() {
+        assert_eq!(Value::get_scientific(&BigInt::from(1_000_000)), "1e6");
+        assert_eq!(Value::get_scientific(&BigInt::from(-123_400)), "-1.234e5");
+        let large = num_traits::pow(BigInt::from(2), 100);
+        assert_eq!(Value::get_scientific(&large), "1.267650600228229e30");
+        let json = Value::Integer(large).get_json(Format::default());
+        assert_eq!(json, r#"{"type":"Integer","value":"1267650600228229401496703205376","scientific":"1.267650600228229e30"}"#);
+        assert_eq!(Value::Integer(BigInt::from(42)).get_json(Format::default()), r#"{"type":"Integer","value":"42"}"#);
+    }
+
+    #[test]
+    fn rejects_exponents_too_large_to_expand() {
+        assert_eq!(Value::parse_rational("1e100000").map(|rational| rational.numer().bits()), Some(332_193));
+        assert_eq!(Value::parse_rational("1e100001"), None);
+        assert_eq!(Value::parse_rational("1e-100000000"), None);
+        assert_eq!(Value::parse_rational("1e99999999999999999999"), None);
+    }
+
+    #[test]
+    fn escapes_strings_in_json() {
+        let amount = BigRational::from_integer(BigInt::from(5));
+        let timestamp = Some(String::from("C:\\rates \"daily\"\t\u{1}"));
+        let money = Value::Money(Money { amount, currency: get_currency("EUR").unwrap(), timestamp });
+        let json = money.get_json(Format::default());
+        assert!(json.ends_with(r#","timestamp":"C:\\rates \"daily\"\t\u0001"}"#), "{}", json);
+        let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(json["timestamp"], "C:\\rates \"daily\"\t\u{1}");
+        assert_eq!(json["value"], "5.00 EUR");
+    }
+}